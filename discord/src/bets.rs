@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use poise::serenity_prelude::{GuildId, MessageId, UserId};
+use redis::{AsyncCommands, RedisResult, Script, aio::ConnectionManager};
+
+use crate::wallets::wallet_key;
+
+/// Moves `ARGV[2]` from the wallet of `ARGV[1]` to their bet in `KEYS[2]`.
+///
+/// Returns 0 once placed, -1 if they already bet on the other side in `KEYS[3]`, -2 if
+/// they can't pay, or -3 if betting closed and `KEYS[4]` is gone.
+const PLACE_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[4]) == 0 then
+    return -3
+end
+if redis.call('HEXISTS', KEYS[3], ARGV[1]) == 1 then
+    return -1
+end
+local amount = tonumber(ARGV[2])
+if tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0') < amount then
+    return -2
+end
+redis.call('HINCRBY', KEYS[1], ARGV[1], -amount)
+redis.call('HINCRBY', KEYS[2], ARGV[1], amount)
+return 0
+";
+
+/// Closes the bets in `KEYS[2..=4]` and pays them into `KEYS[1]` when `ARGV[1]` won, see
+/// [`payouts`]. `ARGV[1]` is -1 without a winner.
+///
+/// Returns the bets on each player, empty if they were already paid.
+const SETTLE_SCRIPT: &str = r"
+local pools = {redis.call('HGETALL', KEYS[2]), redis.call('HGETALL', KEYS[3])}
+redis.call('DEL', KEYS[2], KEYS[3], KEYS[4])
+local function sum(pool)
+    local total = 0
+    for i = 2, #pool, 2 do
+        total = total + tonumber(pool[i])
+    end
+    return total
+end
+local total = sum(pools[1]) + sum(pools[2])
+local winner = pools[tonumber(ARGV[1]) + 1]
+if winner and sum(winner) > 0 then
+    local pool = sum(winner)
+    for i = 1, #winner, 2 do
+        redis.call('HINCRBY', KEYS[1], winner[i], math.floor(tonumber(winner[i + 1]) * total / pool))
+    end
+else
+    for _, stakes in ipairs(pools) do
+        for i = 1, #stakes, 2 do
+            redis.call('HINCRBY', KEYS[1], stakes[i], stakes[i + 1])
+        end
+    end
+end
+return pools
+";
+
+/// Bets on one player of a duel, amount by user id.
+fn pool_key(message_id: MessageId, side: usize) -> String {
+    format!("bets:{message_id}:{side}")
+}
+
+/// Set while spectators can bet on a duel.
+fn open_key(message_id: MessageId) -> String {
+    format!("bets:{message_id}:open")
+}
+
+pub enum BetError {
+    /// The bettor already backs the other player.
+    OtherSide,
+    Insufficient,
+    Closed,
+}
+
+/// Coins and number of bets on each player, challenger first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pools {
+    pub amounts: [u64; 2],
+    pub bettors: [usize; 2],
+}
+
+impl Pools {
+    pub fn total(&self) -> u64 {
+        self.amounts.iter().sum()
+    }
+}
+
+/// What every bettor gets back once a duel ends.
+///
+/// Backers of the winner split both pools by stake, odd coins left over are lost. Everyone
+/// gets their stake back when there is no winner or nobody backed them.
+pub fn payouts(stakes: &[Vec<(UserId, u64)>; 2], winner: Option<usize>) -> Vec<(UserId, u64)> {
+    let pool = |side: usize| stakes[side].iter().map(|(_, amount)| amount).sum::<u64>();
+    let total = pool(0) + pool(1);
+
+    match winner {
+        Some(side) if pool(side) > 0 => stakes[side]
+            .iter()
+            .map(|&(user_id, amount)| {
+                let share = amount as u128 * total as u128 / pool(side) as u128;
+                (user_id, share as u64)
+            })
+            .collect(),
+        _ => stakes.iter().flatten().copied().collect(),
+    }
+}
+
+/// Redis-backed bets of spectators on the duels in progress.
+#[derive(Clone)]
+pub struct Bets {
+    con: ConnectionManager,
+}
+
+impl Bets {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    /// Lets spectators bet on the duel posted in `message_id`.
+    pub async fn open(&self, message_id: MessageId) -> RedisResult<()> {
+        let mut con = self.con.clone();
+        con.set(open_key(message_id), chrono::Utc::now().timestamp())
+            .await
+    }
+
+    /// Stops taking bets on the duel posted in `message_id`, the placed ones stay.
+    pub async fn close(&self, message_id: MessageId) -> RedisResult<()> {
+        let mut con = self.con.clone();
+        con.del(open_key(message_id)).await
+    }
+
+    /// Stakes `amount` of `user_id`'s coins on `side` of the duel posted in `message_id`.
+    ///
+    /// Betting again on the same side adds to the bet.
+    pub async fn place(
+        &self,
+        guild_id: GuildId,
+        message_id: MessageId,
+        side: usize,
+        user_id: UserId,
+        amount: u64,
+    ) -> RedisResult<Result<(), BetError>> {
+        let result: i64 = Script::new(PLACE_SCRIPT)
+            .key(wallet_key(guild_id))
+            .key(pool_key(message_id, side))
+            .key(pool_key(message_id, 1 - side))
+            .key(open_key(message_id))
+            .arg(user_id.get())
+            .arg(amount)
+            .invoke_async(&mut self.con.clone())
+            .await?;
+
+        Ok(match result {
+            0 => Ok(()),
+            -1 => Err(BetError::OtherSide),
+            -2 => Err(BetError::Insufficient),
+            _ => Err(BetError::Closed),
+        })
+    }
+
+    pub async fn pools(&self, message_id: MessageId) -> RedisResult<Pools> {
+        let (challenger, opponent): (Vec<u64>, Vec<u64>) = redis::pipe()
+            .hvals(pool_key(message_id, 0))
+            .hvals(pool_key(message_id, 1))
+            .query_async(&mut self.con.clone())
+            .await?;
+
+        Ok(Pools {
+            amounts: [challenger.iter().sum(), opponent.iter().sum()],
+            bettors: [challenger.len(), opponent.len()],
+        })
+    }
+
+    /// Closes the bets on a duel and pays them out, see [`payouts`].
+    ///
+    /// Returns the pools and the payouts, bets are only ever paid once and none are taken
+    /// after.
+    pub async fn settle(
+        &self,
+        guild_id: GuildId,
+        message_id: MessageId,
+        winner: Option<usize>,
+    ) -> RedisResult<(Pools, Vec<(UserId, u64)>)> {
+        let (challenger, opponent): (HashMap<u64, u64>, HashMap<u64, u64>) =
+            Script::new(SETTLE_SCRIPT)
+                .key(wallet_key(guild_id))
+                .key(pool_key(message_id, 0))
+                .key(pool_key(message_id, 1))
+                .key(open_key(message_id))
+                .arg(winner.map_or(-1, |side| side as i64))
+                .invoke_async(&mut self.con.clone())
+                .await?;
+
+        let stakes = [challenger, opponent].map(|pool| {
+            pool.into_iter()
+                .map(|(user_id, amount)| (UserId::new(user_id), amount))
+                .collect::<Vec<_>>()
+        });
+        let pools = Pools {
+            amounts: [0, 1].map(|side| stakes[side].iter().map(|(_, amount)| amount).sum()),
+            bettors: [0, 1].map(|side| stakes[side].len()),
+        };
+        // What the script paid.
+        Ok((pools, payouts(&stakes, winner)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64) -> UserId {
+        UserId::new(id)
+    }
+
+    #[test]
+    fn winners_split_both_pools_by_stake() {
+        let stakes = [vec![(user(1), 100), (user(2), 300)], vec![(user(3), 400)]];
+        assert_eq!(
+            payouts(&stakes, Some(0)),
+            vec![(user(1), 200), (user(2), 600)]
+        );
+        assert_eq!(payouts(&stakes, Some(1)), vec![(user(3), 800)]);
+    }
+
+    #[test]
+    fn odd_coins_round_down() {
+        let stakes = [
+            vec![(user(1), 1), (user(2), 1), (user(3), 1)],
+            vec![(user(4), 1)],
+        ];
+        let paid: u64 = payouts(&stakes, Some(0))
+            .iter()
+            .map(|(_, amount)| amount)
+            .sum();
+        assert_eq!(paid, 3);
+    }
+
+    #[test]
+    fn refunds_when_nobody_backed_the_winner() {
+        let stakes = [vec![(user(1), 50)], vec![]];
+        assert_eq!(payouts(&stakes, Some(1)), vec![(user(1), 50)]);
+        assert_eq!(payouts(&stakes, None), vec![(user(1), 50)]);
+    }
+}
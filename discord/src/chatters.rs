@@ -0,0 +1,105 @@
+use poise::serenity_prelude::MessageId;
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+
+const CHATTERS_KEY: &str = "twitch:chatters";
+const MAX_CHATTERS: isize = 500;
+const MAX_HISTORY: isize = 20;
+const RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// What the relay knows about a Twitch line it posted on Discord.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayedMessage {
+    pub twitch_user_id: String,
+    pub login: String,
+    pub display_name: String,
+    pub message_id: String,
+    pub message: String,
+    /// Milliseconds since the epoch, as sent by Twitch.
+    pub timestamp: i64,
+}
+
+fn relay_key(discord_message_id: MessageId) -> String {
+    format!("relay:{discord_message_id}")
+}
+
+fn history_key(login: &str) -> String {
+    format!("twitch:history:{}", login.to_lowercase())
+}
+
+/// Twitch users recently seen in chat, most recent first, used for autocompletion,
+/// along with the messages the relay posted for them.
+#[derive(Clone)]
+pub struct Chatters {
+    con: ConnectionManager,
+}
+
+impl Chatters {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    pub async fn record(&self, login: &str) -> RedisResult<()> {
+        let mut con = self.con.clone();
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let _: () = con.zadd(CHATTERS_KEY, login.to_lowercase(), now).await?;
+        let _: () = con
+            .zremrangebyrank(CHATTERS_KEY, 0, -(MAX_CHATTERS + 1))
+            .await?;
+        Ok(())
+    }
+
+    /// Remembers which Twitch line a relayed Discord message came from.
+    pub async fn record_relay(
+        &self,
+        discord_message_id: MessageId,
+        relayed: &RelayedMessage,
+    ) -> RedisResult<()> {
+        let mut con = self.con.clone();
+
+        let raw = serde_json::to_string(relayed).unwrap_or_default();
+        let history = history_key(&relayed.login);
+
+        let _: () = con
+            .set_ex(relay_key(discord_message_id), &raw, RETENTION_SECS as u64)
+            .await?;
+        let _: () = con.lpush(&history, &raw).await?;
+        let _: () = con.ltrim(&history, 0, MAX_HISTORY - 1).await?;
+        let _: () = con.expire(&history, RETENTION_SECS).await?;
+        Ok(())
+    }
+
+    pub async fn relayed(
+        &self,
+        discord_message_id: MessageId,
+    ) -> RedisResult<Option<RelayedMessage>> {
+        let mut con = self.con.clone();
+
+        let raw: Option<String> = con.get(relay_key(discord_message_id)).await?;
+        Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+    }
+
+    /// Latest messages of a chatter, most recent first.
+    pub async fn history(&self, login: &str) -> RedisResult<Vec<RelayedMessage>> {
+        let mut con = self.con.clone();
+
+        let raw: Vec<String> = con.lrange(history_key(login), 0, MAX_HISTORY - 1).await?;
+        Ok(raw
+            .iter()
+            .filter_map(|raw| serde_json::from_str(raw).ok())
+            .collect())
+    }
+
+    pub async fn search(&self, partial: &str, limit: usize) -> RedisResult<Vec<String>> {
+        let mut con = self.con.clone();
+
+        let partial = partial.to_lowercase();
+        let recent: Vec<String> = con.zrevrange(CHATTERS_KEY, 0, -1).await?;
+        Ok(recent
+            .into_iter()
+            .filter(|login| login.starts_with(&partial))
+            .take(limit)
+            .collect())
+    }
+}
@@ -0,0 +1,246 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use poise::serenity_prelude::{self as serenity, GuildId};
+use redis::AsyncCommands;
+use serde_json::{Value, json};
+
+use crate::{Error, cmds, config::Config};
+
+/// Discord side of the Synapse Twitch bridge.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Starts the bot (default)
+    Run,
+    /// Registers the slash commands, globally or in one guild
+    Register {
+        /// Guild to register the commands in, they show up instantly there
+        #[arg(long)]
+        guild: Option<u64>,
+    },
+    /// Removes the slash commands, globally or from one guild
+    Unregister {
+        #[arg(long)]
+        guild: Option<u64>,
+    },
+    /// Validates the configuration and prints it
+    CheckConfig,
+    /// Prints every command the bot registers
+    ListCommands,
+    /// Publishes a sample Twitch event on the Redis bus
+    Simulate {
+        event: SampleEvent,
+        /// Twitch channel of the event, the configured one by default
+        #[arg(long)]
+        channel: Option<String>,
+        /// Twitch login of the user behind the event
+        #[arg(long, default_value = "synapse_tester")]
+        user: String,
+        #[arg(long, default_value = "Hello from the simulator!")]
+        message: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SampleEvent {
+    Message,
+    Cheer,
+    Raided,
+    Resub,
+    Subgift,
+    Subscription,
+    Anongiftpaidupdate,
+}
+
+/// Discord API client for commands that don't connect to the gateway.
+async fn http_client(config: &Config) -> Result<serenity::Http, Error> {
+    let http_client = serenity::Http::new(&config.discord_token);
+    let application = http_client.get_current_application_info().await?;
+    http_client.set_application_id(application.id);
+    Ok(http_client)
+}
+
+/// Registers the commands, in `guild` if set, and returns how many were registered.
+pub async fn register(config: &Config, guild: Option<GuildId>) -> Result<usize, Error> {
+    let http_client = http_client(config).await?;
+    let commands = poise::builtins::create_application_commands(&cmds::get_all_commands());
+    let count = commands.len();
+
+    match guild {
+        Some(guild_id) => {
+            guild_id.set_commands(&http_client, commands).await?;
+        }
+        None => {
+            serenity::Command::set_global_commands(&http_client, commands).await?;
+        }
+    }
+    Ok(count)
+}
+
+pub async fn unregister(config: &Config, guild: Option<GuildId>) -> Result<(), Error> {
+    let http_client = http_client(config).await?;
+
+    match guild {
+        Some(guild_id) => {
+            guild_id.set_commands(&http_client, vec![]).await?;
+        }
+        None => {
+            serenity::Command::set_global_commands(&http_client, vec![]).await?;
+        }
+    }
+    Ok(())
+}
+
+pub fn check_config(config: &Config) {
+    let (r, g, b) = config.color;
+    println!("Configuration is valid.");
+    println!("  prefix:          {}", config.discord_prefix);
+    println!("  color:           #{r:02x}{g:02x}{b:02x}");
+    println!("  locale:          {}", config.locale);
+    println!("  bridge channel:  {}", show(config.bridge_channel));
+    println!(
+        "  twitch channel:  {}",
+        show(config.twitch_channel.as_ref())
+    );
+    println!("  redis:           {}", config.redis_url);
+    println!("  twitch mod role: {}", show(config.twitch_mod_role));
+    println!("  metrics:         {}", show(config.metrics_addr));
+    println!("  test guild:      {}", show(config.test_guild));
+    println!("  role sync guild: {}", show(config.role_sync.guild_id));
+    println!("  disabled events: {:?}", config.disabled_events);
+    println!("  disabled features: {:?}", config.disabled_features);
+}
+
+fn show<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| String::from("-"), |value| value.to_string())
+}
+
+pub fn list_commands() {
+    fn print(command: &poise::Command<crate::Data, Error>, depth: usize) {
+        let kind = match (command.slash_action, command.context_menu_action) {
+            (_, Some(_)) => "context menu",
+            (Some(_), None) => "slash",
+            (None, None) => "group",
+        };
+        println!(
+            "{:indent$}{:<width$} {:<12} {:<12} {}",
+            "",
+            command
+                .context_menu_name
+                .as_deref()
+                .unwrap_or(&command.name),
+            kind,
+            command.category.as_deref().unwrap_or("-"),
+            command.description.as_deref().unwrap_or(""),
+            indent = depth * 2,
+            width = 24 - depth * 2,
+        );
+        for subcommand in &command.subcommands {
+            print(subcommand, depth + 1);
+        }
+    }
+
+    for command in &cmds::get_all_commands() {
+        print(command, 0);
+    }
+}
+
+/// Publishes a sample event and returns how many listeners received it.
+pub async fn simulate(
+    config: &Config,
+    event: SampleEvent,
+    channel: Option<String>,
+    user: &str,
+    message: &str,
+) -> Result<usize, Error> {
+    let channel = channel
+        .or_else(|| config.twitch_channel.clone())
+        .unwrap_or_else(|| user.to_string());
+    let channel = format!("#{}", channel.trim_start_matches('#'));
+
+    let payload = json!({
+        "eventType": event.name(),
+        "data": event.data(&channel, user, message),
+    });
+
+    let client = redis::Client::open(config.redis_url.as_str())?;
+    let mut con = client.get_multiplexed_async_connection().await?;
+    let receivers: usize = con.publish("twitch_events", payload.to_string()).await?;
+    Ok(receivers)
+}
+
+impl SampleEvent {
+    fn name(self) -> &'static str {
+        match self {
+            SampleEvent::Message => "message",
+            SampleEvent::Cheer => "cheer",
+            SampleEvent::Raided => "raided",
+            SampleEvent::Resub => "resub",
+            SampleEvent::Subgift => "subgift",
+            SampleEvent::Subscription => "subscription",
+            SampleEvent::Anongiftpaidupdate => "anongiftpaidupdate",
+        }
+    }
+
+    /// Event data shaped like the Twitch service publishes it.
+    fn data(self, channel: &str, user: &str, message: &str) -> Value {
+        let tags = json!({
+            "username": user,
+            "display-name": user,
+            "user-id": "0",
+            "id": uuid::Uuid::new_v4().to_string(),
+            "tmi-sent-ts": chrono::Utc::now().timestamp_millis().to_string(),
+            "badges": null,
+        });
+
+        match self {
+            SampleEvent::Message => json!({
+                "channel": channel,
+                "tags": tags,
+                "message": message,
+                "self": false,
+            }),
+            SampleEvent::Cheer => json!({
+                "channel": channel,
+                "userstate": { "display-name": user, "username": user, "bits": "100" },
+                "message": message,
+            }),
+            SampleEvent::Raided => json!({
+                "channel": channel,
+                "username": user,
+                "viewers": 42,
+            }),
+            SampleEvent::Resub => json!({
+                "channel": channel,
+                "username": user,
+                "months": 3,
+                "message": message,
+                "userstate": { "msg-param-cumulative-months": "12" },
+            }),
+            SampleEvent::Subgift => json!({
+                "channel": channel,
+                "username": user,
+                "streakMonths": 1,
+                "recipient": "synapse_recipient",
+                "tags": {},
+            }),
+            SampleEvent::Subscription => json!({
+                "channel": channel,
+                "username": user,
+                "methods": { "prime": false, "plan": "1000" },
+                "message": message,
+                "tags": {},
+            }),
+            SampleEvent::Anongiftpaidupdate => json!({
+                "channel": channel,
+                "userstate": { "display-name": user, "username": user },
+                "giftCount": 5,
+            }),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use super::twitch_mod::is_twitch_mod;
+use crate::{
+    Context, Error,
+    rpc::{ChatSetting, RoomState},
+};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+fn on_off(enabled: bool) -> String {
+    if enabled {
+        "**`On`**".into()
+    } else {
+        "`Off`".into()
+    }
+}
+
+fn room_state_fields(state: &RoomState) -> Vec<(&'static str, String, bool)> {
+    vec![
+        (
+            "Slow mode",
+            if state.slow > 0 {
+                format!("**`{}`s**", state.slow)
+            } else {
+                on_off(false)
+            },
+            true,
+        ),
+        ("Emote only", on_off(state.emote_only), true),
+        (
+            "Followers only",
+            if state.followers_only >= 0 {
+                format!("**`{}`min**", state.followers_only)
+            } else {
+                on_off(false)
+            },
+            true,
+        ),
+        ("Subs only", on_off(state.subs_only), true),
+        ("Unique chat", on_off(state.unique_chat), true),
+    ]
+}
+
+/// Applies a chat setting on Twitch and posts the resulting room state
+async fn apply(ctx: Context<'_>, setting: ChatSetting) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let config = ctx.data().settings.config(ctx.guild_id()).await;
+    let result = ctx
+        .data()
+        .rpc
+        .change_chat_setting(
+            setting,
+            config.twitch_channel.as_deref(),
+            &ctx.author().name,
+        )
+        .await;
+
+    let color = config.color;
+
+    let (title, description, fields, color) = match result {
+        Ok(state) => (
+            "Twitch chat settings",
+            None,
+            room_state_fields(&state),
+            serenity::Colour::from_rgb(color.0, color.1, color.2),
+        ),
+        Err(e) => (
+            "❌ Failed",
+            Some(format!("The command failed: `{e}`")),
+            vec![],
+            serenity::Colour::from_rgb(220, 40, 40),
+        ),
+    };
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .author(CreateEmbedAuthor::new(ctx.author().display_name()).icon_url(author_img.to_owned()))
+        .thumbnail(bot_img.to_owned())
+        .fields(fields)
+        .color(color)
+        .footer(CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()))
+        .timestamp(chrono::Utc::now());
+
+    if let Some(description) = description {
+        embed = embed.description(description);
+    }
+
+    ctx.send(CreateReply {
+        embeds: vec![embed],
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Changes the Twitch chat settings
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    subcommands("slow", "emoteonly", "followers", "subonly", "unique"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn chatmode(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Limits how often chatters can send messages
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn slow(
+    ctx: Context<'_>,
+    #[description = "Turn slow mode on or off"] enabled: bool,
+    #[description = "Seconds between messages (default: 30)"]
+    #[min = 1]
+    #[max = 1800]
+    seconds: Option<u32>,
+) -> Result<(), Error> {
+    apply(
+        ctx,
+        ChatSetting::Slow {
+            enabled,
+            seconds: seconds.unwrap_or(30),
+        },
+    )
+    .await
+}
+
+/// Only allows messages made of emotes
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn emoteonly(
+    ctx: Context<'_>,
+    #[description = "Turn emote-only mode on or off"] enabled: bool,
+) -> Result<(), Error> {
+    apply(ctx, ChatSetting::EmoteOnly { enabled }).await
+}
+
+/// Only allows followers to chat
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn followers(
+    ctx: Context<'_>,
+    #[description = "Turn followers-only mode on or off"] enabled: bool,
+    #[description = "Minimum follow age in minutes (default: 0)"]
+    #[max = 129600]
+    minutes: Option<u32>,
+) -> Result<(), Error> {
+    apply(
+        ctx,
+        ChatSetting::FollowersOnly {
+            enabled,
+            minutes: minutes.unwrap_or(0),
+        },
+    )
+    .await
+}
+
+/// Only allows subscribers to chat
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn subonly(
+    ctx: Context<'_>,
+    #[description = "Turn subscribers-only mode on or off"] enabled: bool,
+) -> Result<(), Error> {
+    apply(ctx, ChatSetting::SubsOnly { enabled }).await
+}
+
+/// Rejects messages identical to a recent one
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn unique(
+    ctx: Context<'_>,
+    #[description = "Turn unique chat on or off"] enabled: bool,
+) -> Result<(), Error> {
+    apply(ctx, ChatSetting::UniqueChat { enabled }).await
+}
@@ -0,0 +1,518 @@
+use crate::{
+    Context, Error,
+    config::{EVENTS, FEATURES, Templates},
+    guild_settings::setting_names,
+};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+const AUDIT_PAGE: isize = 10;
+
+async fn autocomplete_events(_ctx: Context<'_>, partial: &str) -> impl Iterator<Item = String> {
+    EVENTS
+        .iter()
+        .filter(move |event| event.starts_with(partial))
+        .map(|event| event.to_string())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+async fn autocomplete_features(_ctx: Context<'_>, partial: &str) -> impl Iterator<Item = String> {
+    FEATURES
+        .iter()
+        .filter(move |feature| feature.starts_with(partial))
+        .map(|feature| feature.to_string())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+async fn autocomplete_templates(_ctx: Context<'_>, partial: &str) -> impl Iterator<Item = String> {
+    Templates::names()
+        .filter(move |name| name.starts_with(partial))
+        .map(String::from)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+async fn autocomplete_settings(_ctx: Context<'_>, partial: &str) -> impl Iterator<Item = String> {
+    setting_names()
+        .into_iter()
+        .filter(move |name| name.contains(partial))
+        .take(25)
+}
+
+/// Shortens `text` to at most `max` bytes without splitting a character.
+fn clip(mut text: String, max: usize) -> String {
+    if text.len() > max {
+        let end = (0..=max - 1)
+            .rev()
+            .find(|i| text.is_char_boundary(*i))
+            .unwrap_or(0);
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+fn show_value(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("`{value}`"),
+        None => String::from("*default*"),
+    }
+}
+
+async fn send_embed(
+    ctx: Context<'_>,
+    title: &str,
+    description: String,
+    fields: Vec<(String, String, bool)>,
+) -> Result<(), Error> {
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title(title)
+                .description(description)
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .fields(fields)
+                .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ephemeral: Some(true),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Stores a setting for this server and confirms the change
+async fn change(ctx: Context<'_>, setting: &str, value: &str) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+    if setting == "twitch_channel" {
+        // Joining the channel can take longer than Discord waits for an answer.
+        ctx.defer_ephemeral().await?;
+    }
+
+    let old = ctx
+        .data()
+        .settings
+        .set(guild_id, ctx.author().id, setting, value)
+        .await?;
+    if setting == "twitch_channel" {
+        sync_twitch_channels(ctx).await;
+    }
+
+    send_embed(
+        ctx,
+        "✅ Setting changed",
+        format!("`{setting}`: {} → `{value}`", show_value(&old)),
+        vec![],
+    )
+    .await
+}
+
+/// Joins the Twitch channels servers picked, the change is saved even if this fails
+async fn sync_twitch_channels(ctx: Context<'_>) {
+    if let Err(e) = ctx
+        .data()
+        .settings
+        .sync_twitch_channels(&ctx.data().rpc, &ctx.author().name)
+        .await
+    {
+        tracing::warn!(error = %e, "Failed to sync Twitch channels");
+    }
+}
+
+/// View and change the bot settings of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    subcommands(
+        "show",
+        "channel",
+        "twitch_channel",
+        "color",
+        "prefix",
+        "feature",
+        "season_days",
+        "betting_turns",
+        "event",
+        "template",
+        "reset",
+        "audit"
+    ),
+    subcommand_required,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows the settings in use on this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+
+    let config = ctx.data().settings.config(Some(guild_id)).await;
+    let overrides = ctx.data().settings.overrides(guild_id).await?;
+    let mark = |setting: &str| {
+        if overrides.contains_key(setting) {
+            " ✏️"
+        } else {
+            ""
+        }
+    };
+
+    let (r, g, b) = config.color;
+    let mut events = String::new();
+    for event in EVENTS {
+        let state = if config.event_enabled(event) {
+            "🟢"
+        } else {
+            "🔴"
+        };
+        events.push_str(&format!(
+            "{state} `{event}`{}\n",
+            mark(&format!("event.{event}"))
+        ));
+    }
+
+    let mut features = String::new();
+    for feature in FEATURES {
+        let state = if config.feature_enabled(feature) {
+            "🟢"
+        } else {
+            "🔴"
+        };
+        features.push_str(&format!(
+            "{state} `{feature}`{}\n",
+            mark(&format!("feature.{feature}"))
+        ));
+    }
+
+    let mut templates = String::new();
+    for name in Templates::names() {
+        if let Some(template) = config.templates.get(name) {
+            templates.push_str(&format!(
+                "**{name}**{}: `{template}`\n",
+                mark(&format!("template.{name}"))
+            ));
+        }
+    }
+
+    let fields = vec![
+        (
+            String::from("Bridge channel"),
+            match config.bridge_channel {
+                Some(channel) => format!("<#{channel}>{}", mark("bridge_channel")),
+                None => String::from("*not set*"),
+            },
+            true,
+        ),
+        (
+            String::from("Twitch channel"),
+            match &config.twitch_channel {
+                Some(channel) => format!("`{channel}`{}", mark("twitch_channel")),
+                None => String::from("*any*"),
+            },
+            true,
+        ),
+        (
+            String::from("Prefix"),
+            format!("`{}`{}", config.discord_prefix, mark("prefix")),
+            true,
+        ),
+        (
+            String::from("Colour"),
+            format!("`#{r:02x}{g:02x}{b:02x}`{}", mark("color")),
+            true,
+        ),
+        (
+            String::from("Duel seasons"),
+            match config.duels.season_days {
+                0 => format!("*until reset*{}", mark("season_days")),
+                days => format!("`{days}` days{}", mark("season_days")),
+            },
+            true,
+        ),
+        (
+            String::from("Duel betting"),
+            match config.duels.betting_turns {
+                0 => format!("*off*{}", mark("betting_turns")),
+                turns => format!("`{turns}` turns{}", mark("betting_turns")),
+            },
+            true,
+        ),
+        (String::from("Features"), features, true),
+        (String::from("Events"), events, true),
+        (String::from("Templates"), clip(templates, 1000), false),
+    ];
+
+    send_embed(
+        ctx,
+        "Server Settings",
+        String::from("✏️ marks settings changed on this server, the rest come from `config.json`."),
+        fields,
+    )
+    .await
+}
+
+/// Sets the channel Twitch chat is relayed to and from
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn channel(
+    ctx: Context<'_>,
+    #[description = "The bridge channel"]
+    #[channel_types("Text")]
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    change(ctx, "bridge_channel", &channel.id.to_string()).await
+}
+
+/// Sets the Twitch channel relayed to this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn twitch_channel(
+    ctx: Context<'_>,
+    #[description = "Name of the Twitch channel"] channel: String,
+) -> Result<(), Error> {
+    change(ctx, "twitch_channel", &channel).await
+}
+
+/// Sets the colour of the bot's embeds
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn color(
+    ctx: Context<'_>,
+    #[description = "Hex colour, e.g. #7289da"] color: String,
+) -> Result<(), Error> {
+    change(ctx, "color", &color).await
+}
+
+/// Sets the prefix of text commands
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn prefix(
+    ctx: Context<'_>,
+    #[description = "The new prefix"] prefix: String,
+) -> Result<(), Error> {
+    change(ctx, "prefix", &prefix).await
+}
+
+/// Turns a part of the bot on or off on this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn feature(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_features"]
+    #[description = "The feature"]
+    feature: String,
+    #[description = "Whether the feature is on"] enabled: bool,
+) -> Result<(), Error> {
+    change(ctx, &format!("feature.{feature}"), &enabled.to_string()).await
+}
+
+/// Sets how many days a ranked duel season lasts, 0 to end seasons only by hand
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn season_days(
+    ctx: Context<'_>,
+    #[description = "Days per season, 0 for no automatic reset"] days: u64,
+) -> Result<(), Error> {
+    change(ctx, "season_days", &days.to_string()).await
+}
+
+/// Sets for how many turns of a duel spectators can bet, 0 to turn betting off
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn betting_turns(
+    ctx: Context<'_>,
+    #[description = "Turns bets stay open, 0 for no betting"] turns: u32,
+) -> Result<(), Error> {
+    change(ctx, "betting_turns", &turns.to_string()).await
+}
+
+/// Turns the relay of a Twitch event on or off
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn event(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_events"]
+    #[description = "The Twitch event"]
+    event: String,
+    #[description = "Whether the event is relayed"] enabled: bool,
+) -> Result<(), Error> {
+    change(ctx, &format!("event.{event}"), &enabled.to_string()).await
+}
+
+/// Changes the message posted for a Twitch event
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn template(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_templates"]
+    #[description = "The template to change"]
+    name: String,
+    #[description = "The message, with {placeholders}"]
+    #[rest]
+    text: String,
+) -> Result<(), Error> {
+    change(ctx, &format!("template.{name}"), &text).await
+}
+
+/// Goes back to the value from config.json
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn reset(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_settings"]
+    #[description = "The setting to reset"]
+    setting: String,
+) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+    if setting == "twitch_channel" {
+        // Leaving the channel can take longer than Discord waits for an answer.
+        ctx.defer_ephemeral().await?;
+    }
+
+    let old = ctx
+        .data()
+        .settings
+        .reset(guild_id, ctx.author().id, &setting)
+        .await?;
+
+    let Some(old) = old else {
+        return Err(Error::User(format!(
+            "`{setting}` is not changed on this server."
+        )));
+    };
+    if setting == "twitch_channel" {
+        sync_twitch_channels(ctx).await;
+    }
+
+    send_embed(
+        ctx,
+        "✅ Setting reset",
+        format!("`{setting}`: `{old}` → *default*"),
+        vec![],
+    )
+    .await
+}
+
+/// Shows the latest setting changes on this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn audit(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+
+    let entries = ctx.data().settings.audit_log(guild_id, AUDIT_PAGE).await?;
+
+    let mut description = String::new();
+    for entry in &entries {
+        description.push_str(&format!(
+            "<t:{}:R> <@{}> `{}`: {} → {}\n",
+            entry.timestamp,
+            entry.user_id,
+            entry.setting,
+            show_value(&entry.old),
+            show_value(&entry.new)
+        ));
+    }
+    if description.is_empty() {
+        description = String::from("No changes yet.");
+    }
+
+    send_embed(ctx, "Settings Audit Log", clip(description, 4000), vec![]).await
+}
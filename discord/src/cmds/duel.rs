@@ -1,4 +1,4 @@
-use crate::{Context, Error, GameState};
+use crate::{Context, Error, GameState, duels::engine::Duel, wallets::CURRENCY};
 use poise::{
     CreateReply,
     serenity_prelude::{
@@ -18,20 +18,61 @@ use poise::{
 pub async fn duel(
     ctx: Context<'_>,
     #[description = "The user to duel against"] user: serenity::User,
+    #[description = "Coins each of you puts in, the winner takes both"]
+    #[min = 1]
+    wager: Option<u64>,
 ) -> Result<(), Error> {
     let challenger_id = ctx.author().id;
     let opponent_id = user.id;
 
-    if challenger_id == opponent_id || user.bot {
-        return Err("You can't duel yourself or a bot!".into());
+    if user.bot {
+        return Err(Error::user("You can't duel a bot!"));
+    }
+    let profiles = &ctx.data().profiles;
+    let stats = [
+        profiles.get(challenger_id).await?.stats(),
+        profiles.get(opponent_id).await?.stats(),
+    ];
+    let duel = Duel::challenge(challenger_id.get(), opponent_id.get(), stats)
+        .map_err(|e| Error::user(e.to_string()))?;
+
+    let config = ctx.data().settings.config(ctx.guild_id()).await;
+    let deadline = config.duels.accept_deadline();
+
+    let wager = wager.unwrap_or_default();
+    if wager > 0 {
+        let Some(guild_id) = ctx.guild_id() else {
+            return Err(Error::user("Wagers only work in a server."));
+        };
+        if !config.feature_enabled("economy") {
+            return Err(Error::Permission(String::from(
+                "The `economy` feature is turned off on this server.",
+            )));
+        }
+        let wallets = &ctx.data().wallets;
+        let grant = config.economy.starting_grant;
+        wallets.open(guild_id, opponent_id, grant).await?;
+        // Coins are only taken once the challenge is accepted, this just catches the obvious.
+        let balance = wallets.open(guild_id, challenger_id, grant).await?;
+        if balance < wager {
+            return Err(Error::User(format!(
+                "You only have **{balance}** {CURRENCY}."
+            )));
+        }
     }
 
     let initial_state = GameState {
-        player1_id: challenger_id,
-        player2_id: opponent_id,
-        player1_hp: 100,
-        player2_hp: 100,
-        turn: challenger_id,
+        duel,
+        guild_id: ctx.guild_id(),
+        channel_id: ctx.channel_id(),
+        deadline,
+        wager,
+    };
+
+    let stakes = if wager > 0 {
+        format!("\n\nBoth of you put in **{wager}** {CURRENCY}, the winner takes it all.")
+    } else {
+        String::new()
     };
 
     let thumbnail = user.avatar_url().unwrap_or("".into());
@@ -76,7 +117,7 @@ pub async fn duel(
                     )
                     .thumbnail(thumbnail.to_owned())
                     .description(format!(
-                        "{} has challenged {} to a duel ! Press 'Accept' to take up arms, or 'Cancel' if your cowardice surpasses you.",
+                        "{} has challenged {} to a duel ! Press 'Accept' to take up arms, or 'Cancel' if your cowardice surpasses you.\n\nThe challenge expires <t:{deadline}:R>.{stakes}",
                         ctx.author(),
                         user
                     ))
@@ -93,10 +134,9 @@ pub async fn duel(
         .await?;
 
     ctx.data()
-        .active_duels
-        .lock()
-        .await
-        .insert(duel_message.message().await?.id, initial_state);
+        .duels
+        .insert(duel_message.message().await?.id, initial_state)
+        .await?;
 
     Ok(())
 }
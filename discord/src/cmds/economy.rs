@@ -0,0 +1,170 @@
+use crate::{Context, Error, wallets::CURRENCY};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+async fn send_embed(ctx: Context<'_>, title: &str, description: String) -> Result<(), Error> {
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title(title)
+                .description(description)
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Shows how many coins a member has on this server
+#[poise::command(slash_command, prefix_command, category = "Economy", guild_only)]
+pub async fn balance(
+    ctx: Context<'_>,
+    #[description = "The member to look up, yourself by default"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+    if user.bot {
+        return Err(Error::user("Bots don't have coins!"));
+    }
+
+    // Only members opening their own wallet get the starting grant.
+    let balance = if user.id == ctx.author().id {
+        let starting_grant = ctx
+            .data()
+            .settings
+            .config(Some(guild_id))
+            .await
+            .economy
+            .starting_grant;
+        ctx.data()
+            .wallets
+            .open(guild_id, user.id, starting_grant)
+            .await?
+    } else {
+        ctx.data().wallets.balance(guild_id, user.id).await?
+    };
+    send_embed(
+        ctx,
+        "Balance",
+        format!("{user} has **{balance}** {CURRENCY}."),
+    )
+    .await
+}
+
+/// Claims your daily coins
+#[poise::command(slash_command, prefix_command, category = "Economy", guild_only)]
+pub async fn daily(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+
+    let economy = ctx
+        .data()
+        .settings
+        .config(Some(guild_id))
+        .await
+        .economy
+        .clone();
+    let amount = economy.daily_amount;
+    ctx.data()
+        .wallets
+        .open(guild_id, ctx.author().id, economy.starting_grant)
+        .await?;
+    let claimed = ctx
+        .data()
+        .wallets
+        .claim_daily(guild_id, ctx.author().id, amount)
+        .await?;
+
+    match claimed {
+        Ok(balance) => {
+            send_embed(
+                ctx,
+                "Daily Coins",
+                format!(
+                    "You claimed **{amount}** {CURRENCY}, you now have **{balance}** {CURRENCY}."
+                ),
+            )
+            .await
+        }
+        Err(wait_secs) => Err(Error::User(format!(
+            "You already claimed your daily coins, come back <t:{}:R>.",
+            chrono::Utc::now().timestamp() + wait_secs as i64
+        ))),
+    }
+}
+
+/// Gives some of your coins to another member
+#[poise::command(slash_command, prefix_command, category = "Economy", guild_only)]
+pub async fn give(
+    ctx: Context<'_>,
+    #[description = "The member to give coins to"] user: serenity::User,
+    #[description = "How many coins"]
+    #[min = 1]
+    amount: u64,
+) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+    if user.id == ctx.author().id || user.bot {
+        return Err(Error::user("You can't give coins to yourself or a bot!"));
+    }
+    if amount == 0 {
+        return Err(Error::user("Give at least one coin."));
+    }
+
+    let starting_grant = ctx
+        .data()
+        .settings
+        .config(Some(guild_id))
+        .await
+        .economy
+        .starting_grant;
+    let wallets = &ctx.data().wallets;
+    wallets
+        .open(guild_id, ctx.author().id, starting_grant)
+        .await?;
+    if !wallets
+        .transfer(guild_id, ctx.author().id, user.id, amount)
+        .await?
+    {
+        let balance = wallets.balance(guild_id, ctx.author().id).await?;
+        return Err(Error::User(format!(
+            "You only have **{balance}** {CURRENCY}."
+        )));
+    }
+
+    tracing::info!(guild = %guild_id, from = %ctx.author().id, to = %user.id, amount, "Coins given");
+    send_embed(
+        ctx,
+        "Coins Given",
+        format!("{} gave **{amount}** {CURRENCY} to {user}.", ctx.author()),
+    )
+    .await
+}
@@ -32,10 +32,7 @@ pub async fn help(
 
         let c = match cmds.iter().find(|c| c.name == cmd) {
             Some(c) => c,
-            None => {
-                ctx.say(format!("Error: Command `{cmd}` no found.")).await?;
-                return Ok(());
-            }
+            None => return Err(Error::User(format!("Command `{cmd}` not found."))),
         };
         let name = c.name.as_str();
         let desc = c.description.as_deref().unwrap_or("None");
@@ -105,7 +102,7 @@ pub async fn help(
 
     let author_img = ctx.author().avatar_url().unwrap_or("".into());
 
-    let color = ctx.data().color;
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
 
     let mut embed = CreateEmbed::new()
         .title(title)
@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use crate::{Context, Error, ratings::Season};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        self as serenity, ButtonStyle, ComponentInteractionCollector, CreateActionRow,
+        CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, GuildId,
+    },
+};
+
+const PAGE_SIZE: usize = 10;
+/// How long the page buttons keep working after the last click.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Rankings of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "RPG",
+    subcommands("duels", "reset"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn leaderboard(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+async fn season(ctx: Context<'_>, guild_id: GuildId) -> Result<Season, Error> {
+    let season_days = ctx
+        .data()
+        .settings
+        .config(Some(guild_id))
+        .await
+        .duels
+        .season_days;
+    Ok(ctx.data().ratings.season(guild_id, season_days).await?)
+}
+
+async fn leaderboard_embed(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    season: &Season,
+    page: usize,
+    pages: usize,
+) -> Result<CreateEmbed, Error> {
+    let entries = ctx
+        .data()
+        .ratings
+        .page(guild_id, season, page * PAGE_SIZE, PAGE_SIZE)
+        .await?;
+
+    let mut description = String::new();
+    for (i, (user_id, rating)) in entries.iter().enumerate() {
+        let rank = page * PAGE_SIZE + i + 1;
+        let rank = match rank {
+            1 => String::from("🥇"),
+            2 => String::from("🥈"),
+            3 => String::from("🥉"),
+            rank => format!("**{rank}.**"),
+        };
+        description.push_str(&format!("{rank} <@{user_id}> — {rating}\n"));
+    }
+    if description.is_empty() {
+        description = String::from("Nobody finished a duel this season yet.\n");
+    }
+    description.push_str(&format!(
+        "\nSeason started <t:{}:R>, page {}/{pages}",
+        season.started,
+        page + 1
+    ));
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(Some(guild_id)).await.color;
+
+    Ok(CreateEmbed::new()
+        .title(format!("Duel Leaderboard, Season {}", season.number))
+        .author(CreateEmbedAuthor::new(ctx.author().display_name()).icon_url(author_img))
+        .thumbnail(bot_img.to_owned())
+        .description(description)
+        .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+        .footer(CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img))
+        .timestamp(chrono::Utc::now()))
+}
+
+fn page_buttons(ctx_id: u64, page: usize, pages: usize) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{ctx_id}:prev"))
+            .style(ButtonStyle::Secondary)
+            .emoji('◀')
+            .disabled(page == 0),
+        CreateButton::new(format!("{ctx_id}:next"))
+            .style(ButtonStyle::Secondary)
+            .emoji('▶')
+            .disabled(page + 1 >= pages),
+    ])]
+}
+
+/// Shows the best duelists of this season
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "RPG",
+    guild_only,
+    broadcast_typing
+)]
+pub async fn duels(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+
+    let season = season(ctx, guild_id).await?;
+    let ranked = ctx.data().ratings.ranked(guild_id, &season).await?;
+    let pages = ranked.div_ceil(PAGE_SIZE).max(1);
+    let mut page = 0;
+
+    let ctx_id = ctx.id();
+    let mut embed = leaderboard_embed(ctx, guild_id, &season, page, pages).await?;
+    let reply = ctx
+        .send(CreateReply {
+            embeds: vec![embed.clone()],
+            components: (pages > 1).then(|| page_buttons(ctx_id, page, pages)),
+            ..Default::default()
+        })
+        .await?;
+    if pages <= 1 {
+        return Ok(());
+    }
+
+    let shutdown = ctx.data().shutdown.token();
+    loop {
+        let collector = ComponentInteractionCollector::new(ctx)
+            .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+            .timeout(PAGE_TIMEOUT);
+        // Stops waiting for clicks when the bot shuts down.
+        let press = tokio::select! {
+            press = collector.next() => press,
+            _ = shutdown.cancelled() => None,
+        };
+        let Some(press) = press else {
+            break;
+        };
+
+        page = match press.data.custom_id.rsplit(':').next() {
+            Some("prev") => page.saturating_sub(1),
+            Some("next") => (page + 1).min(pages - 1),
+            _ => page,
+        };
+
+        embed = leaderboard_embed(ctx, guild_id, &season, page, pages).await?;
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(embed.clone())
+                        .components(page_buttons(ctx_id, page, pages)),
+                ),
+            )
+            .await?;
+    }
+
+    // Edits replace the embeds too, the last page stays up without its buttons.
+    reply
+        .edit(
+            ctx,
+            CreateReply {
+                embeds: vec![embed],
+                components: Some(vec![]),
+                ..Default::default()
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Ends the current duel season, everyone starts the next one at the default rating
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "RPG",
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Err(Error::user("This command only works in a server."));
+    };
+
+    let current = season(ctx, guild_id).await?;
+    let next = ctx.data().ratings.next_season(guild_id, current).await?;
+    tracing::info!(guild = %guild_id, user = %ctx.author().id, season = next.number, "Duel season reset");
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "✅ Season {} ended, season {} starts now.",
+                current.number, next.number
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use crate::{Context, Error, links::LINK_COMMAND};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+async fn send_embed(
+    ctx: Context<'_>,
+    title: &str,
+    fields: Vec<(&str, String, bool)>,
+    description: Option<String>,
+    ephemeral: bool,
+) -> Result<(), Error> {
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .author(CreateEmbedAuthor::new(ctx.author().display_name()).icon_url(author_img.to_owned()))
+        .thumbnail(bot_img.to_owned())
+        .fields(fields)
+        .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+        .footer(CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()))
+        .timestamp(chrono::Utc::now());
+
+    if let Some(description) = description {
+        embed = embed.description(description);
+    }
+
+    ctx.send(CreateReply {
+        embeds: vec![embed],
+        ephemeral: Some(ephemeral),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Links your Discord account to another platform
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch",
+    subcommands("twitch"),
+    subcommand_required
+)]
+pub async fn link(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Generates a one-time code to link your Twitch account
+#[poise::command(slash_command, prefix_command, category = "Twitch")]
+pub async fn twitch(ctx: Context<'_>) -> Result<(), Error> {
+    let code = ctx.data().links.create_code(ctx.author().id).await?;
+
+    send_embed(
+        ctx,
+        "Link your Twitch account",
+        vec![("Code", format!("**`{code}`**"), false)],
+        Some(format!(
+            "Type `{LINK_COMMAND} {code}` in the Twitch chat within 10 minutes to finish linking your account."
+        )),
+        true,
+    )
+    .await
+}
+
+/// Removes the link between your Discord and Twitch accounts
+#[poise::command(slash_command, prefix_command, category = "Twitch")]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let description = match ctx.data().links.unlink(ctx.author().id).await? {
+        Some(account) => {
+            ctx.data().rolesync.forget(ctx.author().id).await?;
+            format!(
+                "Your Discord account is no longer linked to `{}`.",
+                account.display_name
+            )
+        }
+        None => "Your Discord account is not linked to any Twitch account.".into(),
+    };
+
+    send_embed(ctx, "Unlink", vec![], Some(description), true).await
+}
+
+/// Shows which Twitch account a Discord user is linked to, or the other way around
+#[poise::command(slash_command, prefix_command, category = "Twitch", broadcast_typing)]
+pub async fn whois(
+    ctx: Context<'_>,
+    #[description = "The Discord user to look up"] user: Option<serenity::User>,
+    #[description = "The Twitch username to look up"] twitch: Option<String>,
+) -> Result<(), Error> {
+    let links = &ctx.data().links;
+
+    let discord_id = match (&user, &twitch) {
+        (_, Some(login)) => links.discord_user_by_login(login).await?,
+        (Some(user), None) => Some(user.id),
+        (None, None) => Some(ctx.author().id),
+    };
+
+    let account = match discord_id {
+        Some(id) => links.twitch_account(id).await?,
+        None => None,
+    };
+
+    let (discord_id, account) = match (discord_id, account) {
+        (Some(id), Some(account)) => (id, account),
+        _ => {
+            return send_embed(
+                ctx,
+                "Whois",
+                vec![],
+                Some("No linked account found.".into()),
+                false,
+            )
+            .await;
+        }
+    };
+
+    let fields = vec![
+        ("Discord", format!("<@{discord_id}>"), true),
+        (
+            "Twitch",
+            format!(
+                "[`{}`](https://twitch.tv/{})",
+                account.display_name, account.login
+            ),
+            true,
+        ),
+    ];
+
+    send_embed(ctx, "Whois", fields, None, false).await
+}
@@ -1,7 +1,71 @@
+mod chatmode;
+mod config;
 mod duel;
+mod economy;
 mod help;
+mod leaderboard;
+mod link;
 mod ping;
+mod profile;
+mod rolesync;
+mod status;
+mod twitch_context;
+mod twitch_mod;
+
+use crate::{Context, Error};
+
+/// Feature a command category belongs to, guilds can turn them off with `/config feature`.
+fn category_feature(category: &str) -> Option<&'static str> {
+    match category {
+        "RPG" => Some("duels"),
+        "Economy" => Some("economy"),
+        "Twitch" => Some("linking"),
+        "Twitch Mod" => Some("moderation"),
+        _ => None,
+    }
+}
+
+/// Refuses commands of the features turned off on this server
+pub async fn feature_check(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(feature) = ctx.command().category.as_deref().and_then(category_feature) else {
+        return Ok(true);
+    };
+    if ctx
+        .data()
+        .settings
+        .config(ctx.guild_id())
+        .await
+        .feature_enabled(feature)
+    {
+        Ok(true)
+    } else {
+        Err(Error::Permission(format!(
+            "The `{feature}` feature is turned off on this server."
+        )))
+    }
+}
 
 pub fn get_all_commands() -> Vec<poise::Command<super::Data, super::Error>> {
-    vec![ping::ping(), help::help(), duel::duel()]
+    vec![
+        ping::ping(),
+        status::status(),
+        help::help(),
+        duel::duel(),
+        profile::profile(),
+        leaderboard::leaderboard(),
+        economy::balance(),
+        economy::daily(),
+        economy::give(),
+        link::link(),
+        link::unlink(),
+        link::whois(),
+        rolesync::rolesync(),
+        config::config(),
+        twitch_mod::twitch(),
+        chatmode::chatmode(),
+        twitch_context::timeout_message(),
+        twitch_context::ban_message(),
+        twitch_context::delete_message(),
+        twitch_context::chatter_history(),
+    ]
 }
@@ -8,8 +8,16 @@ use poise::{
 /// Displays latency
 #[poise::command(slash_command, prefix_command, category = "Misc", broadcast_typing)]
 pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
+    // A slow Twitch service would otherwise miss the interaction deadline.
+    ctx.defer().await?;
+
     let gateway_latency = ctx.ping().await.as_millis();
 
+    let twitch_latency = match ctx.data().rpc.ping(&ctx.author().name).await {
+        Ok(ms) => format!("**`{ms}`ms**"),
+        Err(e) => format!("`{e}`"),
+    };
+
     let interaction_timestamp = ctx.created_at();
     let latency_ms = Utc::now()
         .signed_duration_since(*interaction_timestamp)
@@ -25,7 +33,7 @@ pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
 
     let author_img = ctx.author().avatar_url().unwrap_or("".into());
 
-    let color = ctx.data().color;
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
 
     let fields = vec![
         (
@@ -34,6 +42,7 @@ pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
             true,
         ),
         ("User Latency", format!("**`{latency_ms}`ms**"), true),
+        ("Twitch Latency", twitch_latency, true),
         (
             "Disclaimer",
             "-# Negative values are due to Discord's shitty API, not the bot.".into(),
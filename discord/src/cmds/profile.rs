@@ -0,0 +1,74 @@
+use crate::{Context, Error};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+/// Shows the duel record, level and stats of a player
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "RPG",
+    guild_only,
+    broadcast_typing
+)]
+pub async fn profile(
+    ctx: Context<'_>,
+    #[description = "The player to look up, yourself by default"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+    if user.bot {
+        return Err(Error::user("Bots don't duel!"));
+    }
+
+    let profile = ctx.data().profiles.get(user.id).await?;
+    let stats = profile.stats();
+
+    let xp = match profile.next_level_xp() {
+        Some(next) => format!("{} / {next}", profile.xp),
+        None => format!("{} (max level)", profile.xp),
+    };
+    let duels = profile.wins + profile.losses;
+    let win_rate = match duels {
+        0 => String::from("-"),
+        duels => format!("{}%", profile.wins * 100 / duels),
+    };
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+    let thumbnail = user.avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    let embed = CreateEmbed::new()
+        .title(format!("{}'s Profile", user.display_name()))
+        .author(CreateEmbedAuthor::new(ctx.author().display_name()).icon_url(author_img))
+        .thumbnail(thumbnail)
+        .fields(vec![
+            ("Level", profile.level().to_string(), true),
+            ("XP", xp, true),
+            ("\u{200b}", String::from("\u{200b}"), true),
+            ("Wins", profile.wins.to_string(), true),
+            ("Losses", profile.losses.to_string(), true),
+            ("Win Rate", win_rate, true),
+            ("HP", stats.max_hp.to_string(), true),
+            ("Bonus Damage", format!("+{}", stats.bonus_damage), true),
+        ])
+        .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+        .footer(CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img))
+        .timestamp(chrono::Utc::now());
+
+    ctx.send(CreateReply {
+        embeds: vec![embed],
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
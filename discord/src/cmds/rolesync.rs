@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use crate::{Context, Error, rolesync::Tier};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, Member, UserId,
+    },
+};
+
+const MEMBERS_PAGE: u64 = 1000;
+const FIELD_LIMIT: usize = 1000;
+
+fn mention_list(ids: &[UserId]) -> String {
+    if ids.is_empty() {
+        return "None".into();
+    }
+
+    let mut value = String::new();
+    for (i, id) in ids.iter().enumerate() {
+        let mention = format!("<@{id}> ");
+        if value.len() + mention.len() > FIELD_LIMIT {
+            value.push_str(&format!("…and {} more", ids.len() - i));
+            break;
+        }
+        value.push_str(&mention);
+    }
+    value
+}
+
+/// Twitch role synchronisation tools
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    subcommands("audit"),
+    subcommand_required,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_ROLES"
+)]
+pub async fn rolesync(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows members whose Discord roles don't match their Twitch status
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Admin",
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    broadcast_typing
+)]
+pub async fn audit(ctx: Context<'_>) -> Result<(), Error> {
+    let rolesync = &ctx.data().rolesync;
+
+    let Some(guild) = rolesync.guild() else {
+        ctx.say("Role sync is not configured.").await?;
+        return Ok(());
+    };
+
+    let mut members: Vec<Member> = vec![];
+    let mut after = None;
+    loop {
+        let page = guild.members(ctx.http(), Some(MEMBERS_PAGE), after).await?;
+        after = page.last().map(|m| m.user.id);
+        let done = (page.len() as u64) < MEMBERS_PAGE;
+        members.extend(page);
+        if done {
+            break;
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut fields = vec![];
+
+    for tier in Tier::ALL {
+        let Some(role) = rolesync.role(tier) else {
+            continue;
+        };
+
+        let tracked = rolesync.tracked(tier).await?;
+        let tracked_ids: HashSet<UserId> = tracked.iter().map(|(id, _)| *id).collect();
+
+        let missing: Vec<UserId> = tracked
+            .iter()
+            .filter(|(id, until)| {
+                *until > now
+                    && !members
+                        .iter()
+                        .any(|m| m.user.id == *id && m.roles.contains(&role))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        let untracked: Vec<UserId> = members
+            .iter()
+            .filter(|m| m.roles.contains(&role) && !tracked_ids.contains(&m.user.id))
+            .map(|m| m.user.id)
+            .collect();
+
+        fields.push((
+            format!("{} (<@&{role}>) missing the role", tier.name()),
+            mention_list(&missing),
+            false,
+        ));
+        fields.push((
+            format!("{} (<@&{role}>) not backed by Twitch", tier.name()),
+            mention_list(&untracked),
+            false,
+        ));
+    }
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title("Role Sync Audit")
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .description(format!(
+                    "Checked `{}` members against `{}` synced role(s).",
+                    members.len(),
+                    fields.len() / 2
+                ))
+                .fields(fields)
+                .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ephemeral: Some(true),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
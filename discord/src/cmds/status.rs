@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use crate::{Context, Error};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else {
+        format!("{minutes}m {seconds}s")
+    }
+}
+
+fn health(ok: bool, detail: String) -> String {
+    let dot = if ok { "🟢" } else { "🔴" };
+    format!("{dot} {detail}")
+}
+
+/// Displays the health of the Twitch bridge
+#[poise::command(slash_command, prefix_command, category = "Misc", broadcast_typing)]
+pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    // Checking a broken Twitch service takes up to the RPC timeout.
+    ctx.defer().await?;
+    let data = ctx.data();
+
+    let mut con = data.redis.clone();
+    let started = Instant::now();
+    let redis = match redis::cmd("PING").query_async::<String>(&mut con).await {
+        Ok(_) => health(
+            true,
+            format!("Connected (**`{}`ms**)", started.elapsed().as_millis()),
+        ),
+        Err(e) => health(false, format!("`{e}`")),
+    };
+
+    let running = |alive: bool| if alive { "Running" } else { "Stopped" };
+    let listener = health(
+        data.stats.listener_alive() && data.rpc.is_listening(),
+        format!(
+            "Events: `{}`\nReplies: `{}`",
+            running(data.stats.listener_alive()),
+            running(data.rpc.is_listening())
+        ),
+    );
+
+    let twitch = match data.rpc.twitch_status(&ctx.author().name).await {
+        Ok(status) => health(
+            status.state == "OPEN",
+            format!(
+                "`{}` on `{}` for `{}`",
+                status.state,
+                status.channels.join(", "),
+                format_duration(Duration::from_secs(status.uptime_secs))
+            ),
+        ),
+        Err(e) => health(false, format!("`{e}`")),
+    };
+
+    let (to_discord, to_twitch) = data.stats.counts();
+    let failed = data.stats.failed();
+
+    let mut last_events = String::new();
+    for (event_type, at) in data.stats.last_events().await {
+        last_events.push_str(&format!("`{event_type}` <t:{}:R>\n", at.timestamp()));
+    }
+    if last_events.is_empty() {
+        last_events = "None yet".into();
+    }
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    let fields = vec![
+        ("Redis", redis, true),
+        ("Event listener", listener, true),
+        ("Twitch service", twitch, false),
+        (
+            "Relayed",
+            format!(
+                "Twitch → Discord: **`{to_discord}`**\nDiscord → Twitch: **`{to_twitch}`**\nFailed: **`{failed}`**"
+            ),
+            true,
+        ),
+        (
+            "Queue",
+            format!(
+                "Relays in flight: **`{}`**\nPending Twitch calls: **`{}`**",
+                data.stats.in_flight(),
+                data.rpc.pending_count().await
+            ),
+            true,
+        ),
+        ("Last events", last_events, false),
+        (
+            "Uptime",
+            format!("`{}`", format_duration(data.stats.uptime())),
+            true,
+        ),
+        ("Version", format!("`{}`", env!("CARGO_PKG_VERSION")), true),
+    ];
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title("Bridge Status")
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .fields(fields)
+                .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
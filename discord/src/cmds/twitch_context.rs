@@ -0,0 +1,170 @@
+use super::twitch_mod::{is_twitch_mod, moderate};
+use crate::{Context, Error, chatters::RelayedMessage, rpc::ModerationCommand};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+const TIMEOUT_SECS: u32 = 600;
+
+/// Looks up the Twitch line a relayed message came from, telling the user when there is none
+async fn resolve(
+    ctx: Context<'_>,
+    msg: &serenity::Message,
+) -> Result<Option<RelayedMessage>, Error> {
+    let relayed = ctx.data().chatters.relayed(msg.id).await?;
+
+    if relayed.is_none() {
+        ctx.send(
+            CreateReply::default()
+                .content("This message wasn't relayed from Twitch, or it is too old.")
+                .ephemeral(true),
+        )
+        .await?;
+    }
+    Ok(relayed)
+}
+
+/// Times the author of a relayed Twitch message out for 10 minutes
+#[poise::command(
+    context_menu_command = "Timeout on Twitch (10m)",
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn timeout_message(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let Some(relayed) = resolve(ctx, &msg).await? else {
+        return Ok(());
+    };
+
+    let success = format!(
+        "`{}` has been timed out for `{TIMEOUT_SECS}`s.",
+        relayed.display_name
+    );
+    moderate(
+        ctx,
+        ModerationCommand::Timeout {
+            username: relayed.login,
+            duration: TIMEOUT_SECS,
+            reason: None,
+        },
+        success,
+    )
+    .await
+}
+
+/// Bans the author of a relayed Twitch message
+#[poise::command(
+    context_menu_command = "Ban on Twitch",
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn ban_message(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let Some(relayed) = resolve(ctx, &msg).await? else {
+        return Ok(());
+    };
+
+    let success = format!("`{}` has been banned.", relayed.display_name);
+    moderate(
+        ctx,
+        ModerationCommand::Ban {
+            username: relayed.login,
+            reason: None,
+        },
+        success,
+    )
+    .await
+}
+
+/// Deletes a relayed message from the Twitch chat
+#[poise::command(
+    context_menu_command = "Delete on Twitch",
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn delete_message(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let Some(relayed) = resolve(ctx, &msg).await? else {
+        return Ok(());
+    };
+
+    let success = format!(
+        "The message of `{}` has been deleted from Twitch.",
+        relayed.display_name
+    );
+    moderate(
+        ctx,
+        ModerationCommand::Delete {
+            message_id: relayed.message_id,
+        },
+        success,
+    )
+    .await
+}
+
+/// Shows the latest messages of the author of a relayed Twitch message
+#[poise::command(
+    context_menu_command = "Show chatter history",
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn chatter_history(ctx: Context<'_>, msg: serenity::Message) -> Result<(), Error> {
+    let Some(relayed) = resolve(ctx, &msg).await? else {
+        return Ok(());
+    };
+
+    let history = ctx.data().chatters.history(&relayed.login).await?;
+
+    let mut description = String::new();
+    for line in &history {
+        let entry = format!("<t:{}:R> {}\n", line.timestamp / 1000, line.message);
+        if description.len() + entry.len() > 4000 {
+            break;
+        }
+        description.push_str(&entry);
+    }
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    let color = ctx.data().settings.config(ctx.guild_id()).await.color;
+
+    let fields = vec![
+        ("Login", format!("`{}`", relayed.login), true),
+        ("User ID", format!("`{}`", relayed.twitch_user_id), true),
+        ("Messages", format!("`{}`", history.len()), true),
+    ];
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title(format!("{}'s recent messages", relayed.display_name))
+                .url(format!("https://twitch.tv/{}", relayed.login))
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .description(description)
+                .fields(fields)
+                .color(serenity::Colour::from_rgb(color.0, color.1, color.2))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ephemeral: Some(true),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use crate::{Context, Error, rpc::ModerationCommand};
+use poise::{
+    CreateReply,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+
+async fn autocomplete_chatters(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    ctx.data()
+        .chatters
+        .search(partial, 25)
+        .await
+        .unwrap_or_default()
+}
+
+/// Only lets members with the configured Twitch moderator role through
+pub async fn is_twitch_mod(ctx: Context<'_>) -> Result<bool, Error> {
+    match ctx
+        .data()
+        .settings
+        .config(ctx.guild_id())
+        .await
+        .twitch_mod_role
+    {
+        None => Err(Error::permission(
+            "Twitch moderation is not configured on this bot.",
+        )),
+        Some(role) => match ctx.author_member().await {
+            Some(member) if member.roles.contains(&role) => Ok(true),
+            _ => Err(Error::Permission(format!(
+                "You need the <@&{role}> role to moderate the Twitch chat."
+            ))),
+        },
+    }
+}
+
+/// Sends a moderation command to Twitch and reports the outcome
+pub async fn moderate(
+    ctx: Context<'_>,
+    command: ModerationCommand,
+    success: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let channel = ctx
+        .data()
+        .settings
+        .config(ctx.guild_id())
+        .await
+        .twitch_channel
+        .clone();
+    let result = ctx
+        .data()
+        .rpc
+        .moderate(command, channel.as_deref(), &ctx.author().name)
+        .await;
+
+    let (title, description, color) = match result {
+        Ok(()) => ("✅ Done", success, serenity::Colour::from_rgb(0, 200, 80)),
+        Err(e) => (
+            "❌ Failed",
+            format!("The command failed: `{e}`"),
+            serenity::Colour::from_rgb(220, 40, 40),
+        ),
+    };
+
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title(title)
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .description(description)
+                .color(color)
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Moderates the Twitch chat
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    subcommands("timeout", "ban", "unban", "delete", "clear"),
+    subcommand_required,
+    guild_only
+)]
+pub async fn twitch(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Times a Twitch user out
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn timeout(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_chatters"]
+    #[description = "The Twitch user to time out"]
+    username: String,
+    #[description = "Duration in seconds (default: 600)"]
+    #[min = 1]
+    #[max = 1209600]
+    duration: Option<u32>,
+    #[description = "Reason shown to the user"] reason: Option<String>,
+) -> Result<(), Error> {
+    let duration = duration.unwrap_or(600);
+    let success = format!("`{username}` has been timed out for `{duration}`s.");
+
+    moderate(
+        ctx,
+        ModerationCommand::Timeout {
+            username,
+            duration,
+            reason,
+        },
+        success,
+    )
+    .await
+}
+
+/// Bans a Twitch user
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_chatters"]
+    #[description = "The Twitch user to ban"]
+    username: String,
+    #[description = "Reason shown to the user"] reason: Option<String>,
+) -> Result<(), Error> {
+    let success = format!("`{username}` has been banned.");
+
+    moderate(ctx, ModerationCommand::Ban { username, reason }, success).await
+}
+
+/// Unbans a Twitch user
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn unban(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_chatters"]
+    #[description = "The Twitch user to unban"]
+    username: String,
+) -> Result<(), Error> {
+    let success = format!("`{username}` has been unbanned.");
+
+    moderate(ctx, ModerationCommand::Unban { username }, success).await
+}
+
+/// Deletes a single Twitch chat message
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "The ID of the Twitch message"] message_id: String,
+) -> Result<(), Error> {
+    let success = format!("Message `{message_id}` has been deleted.");
+
+    moderate(ctx, ModerationCommand::Delete { message_id }, success).await
+}
+
+/// Clears the whole Twitch chat
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Twitch Mod",
+    guild_only,
+    check = "is_twitch_mod"
+)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    moderate(
+        ctx,
+        ModerationCommand::Clear,
+        "The Twitch chat has been cleared.".into(),
+    )
+    .await
+}
@@ -0,0 +1,918 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use notify::{RecursiveMode, Watcher};
+use poise::serenity_prelude::{ChannelId, GuildId, RoleId};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::{
+    Error, duels::DuelsConfig, rolesync::RoleSyncConfig, template::Template, wallets::EconomyConfig,
+};
+
+const DEFAULT_PREFIX: &str = "&/";
+const DEFAULT_COLOR: (u8, u8, u8) = (114, 137, 218);
+const DEFAULT_REDIS_URL: &str = "redis://redis:6379";
+/// Editors write files in several steps, wait for them to settle before reloading.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Twitch events the bridge can relay, as named on the Redis bus.
+pub const EVENTS: [&str; 7] = [
+    "message",
+    "cheer",
+    "raided",
+    "resub",
+    "subgift",
+    "subscription",
+    "anongiftpaidupdate",
+];
+
+/// Parts of the bot a guild can turn off.
+pub const FEATURES: [&str; 5] = ["bridge", "duels", "economy", "linking", "moderation"];
+
+/// Languages a guild can pick for the bot.
+pub const LOCALES: [&str; 5] = ["en", "de", "es", "fr", "pt"];
+
+const KNOWN_KEYS: [&str; 15] = [
+    "discord_prefix",
+    "color",
+    "locale",
+    "bridge_channel_id",
+    "twitch_channel",
+    "redis_url",
+    "role_sync",
+    "duels",
+    "economy",
+    "twitch_mod_role",
+    "metrics_addr",
+    "test_guild_id",
+    "templates",
+    "events",
+    "features",
+];
+
+/// Messages posted on Discord for each bridged Twitch event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Templates {
+    pub message: Template,
+    pub cheer: Template,
+    pub raided: Template,
+    pub resub: Template,
+    pub subgift: Template,
+    pub subscription: Template,
+    pub anon_gift: Template,
+}
+
+impl Templates {
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        match name {
+            "message" => Some(&self.message),
+            "cheer" => Some(&self.cheer),
+            "raided" => Some(&self.raided),
+            "resub" => Some(&self.resub),
+            "subgift" => Some(&self.subgift),
+            "subscription" => Some(&self.subscription),
+            "anon_gift" => Some(&self.anon_gift),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Template> {
+        match name {
+            "message" => Some(&mut self.message),
+            "cheer" => Some(&mut self.cheer),
+            "raided" => Some(&mut self.raided),
+            "resub" => Some(&mut self.resub),
+            "subgift" => Some(&mut self.subgift),
+            "subscription" => Some(&mut self.subscription),
+            "anon_gift" => Some(&mut self.anon_gift),
+            _ => None,
+        }
+    }
+
+    /// Names of every template, in display order.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        TEMPLATES.iter().map(|(name, _, _)| *name)
+    }
+}
+
+/// `(name, default, placeholders)` of every template.
+const TEMPLATES: [(&str, &str, &[&str]); 7] = [
+    (
+        "message",
+        "**(`{channel}`)** `{user}`{badge}: {message}",
+        &["channel", "user", "badge", "message"],
+    ),
+    (
+        "cheer",
+        "### ✨ `{user}` just cheered with __{bits}__ bits to `{channel}`! Message: \"`{message}`\"",
+        &["channel", "user", "bits", "message"],
+    ),
+    (
+        "raided",
+        "### 🚀 `{user}` started a raid with __{viewers}__ viewers to `{channel}`!",
+        &["channel", "user", "viewers"],
+    ),
+    (
+        "resub",
+        "### ✨ Re-sub from `{user}`! This is their __{months}__ month in a row subscribed to `{channel}`! \"`{message}`\"",
+        &["channel", "user", "months", "message"],
+    ),
+    (
+        "subgift",
+        "### 🎁 `{user}` just gifted `{recipient}` a sub to `{channel}`!",
+        &["channel", "user", "recipient"],
+    ),
+    (
+        "subscription",
+        "### 🎉 A new subscriber! `{user}` just subscribed to `{channel}`! \"`{message}`\"",
+        &["channel", "user", "message"],
+    ),
+    (
+        "anon_gift",
+        "### 🎉 A new subscriber! Anonymous user gifted `{user}` a sub to `{channel}`! (Gift Count: `{gift_count}`)",
+        &["channel", "user", "gift_count"],
+    ),
+];
+
+/// Settings of the bot, read from `config.json` with environment variable overrides.
+///
+/// Built once and validated as a whole: every missing or invalid setting is reported together.
+#[derive(Clone)]
+pub struct Config {
+    pub discord_token: String,
+    pub discord_prefix: String,
+    pub color: (u8, u8, u8),
+    pub locale: String,
+    /// Discord channel Twitch chat is relayed to and from, `None` until a guild picks one.
+    pub bridge_channel: Option<ChannelId>,
+    /// Twitch channel relayed to the bridge channel, any channel the Twitch service joined if `None`.
+    pub twitch_channel: Option<String>,
+    pub redis_url: String,
+    pub role_sync: RoleSyncConfig,
+    pub duels: DuelsConfig,
+    pub economy: EconomyConfig,
+    pub twitch_mod_role: Option<RoleId>,
+    pub metrics_addr: Option<SocketAddr>,
+    /// Guild development builds register their commands in.
+    pub test_guild: Option<GuildId>,
+    pub templates: Templates,
+    /// Events that are not relayed to Discord.
+    pub disabled_events: BTreeSet<String>,
+    pub disabled_features: BTreeSet<String>,
+}
+
+impl Config {
+    /// Loads `path` (optional) and applies the environment on top of it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = match fs::read_to_string(path) {
+            Ok(contents) => match serde_json::from_str::<Value>(&contents) {
+                Ok(Value::Object(file)) => file,
+                Ok(_) => {
+                    return Err(invalid(vec![format!(
+                        "{}: expected a JSON object",
+                        path.display()
+                    )]));
+                }
+                Err(e) => return Err(invalid(vec![format!("{}: {e}", path.display())])),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(invalid(vec![format!("{}: {e}", path.display())])),
+        };
+
+        Self::from_sources(&file, |key| env::var(key).ok().filter(|v| !v.is_empty()))
+    }
+
+    fn from_sources(
+        file: &Map<String, Value>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Error> {
+        let mut problems = vec![];
+
+        for key in file
+            .keys()
+            .filter(|key| !KNOWN_KEYS.contains(&key.as_str()))
+        {
+            tracing::warn!(key, "Ignoring unknown configuration key");
+        }
+
+        let discord_token = env("DISCORD_BOT_TOKEN").unwrap_or_else(|| {
+            problems.push(String::from("DISCORD_BOT_TOKEN: missing"));
+            String::new()
+        });
+
+        let discord_prefix = env("DISCORD_PREFIX")
+            .or_else(|| field(file, "discord_prefix", &mut problems))
+            .unwrap_or_else(|| DEFAULT_PREFIX.into());
+        if discord_prefix.trim().is_empty() {
+            problems.push(String::from("discord_prefix: must not be empty"));
+        }
+
+        let color = match env("BOT_COLOR") {
+            Some(hex) => parse_hex_color(&hex).unwrap_or_else(|e| {
+                problems.push(format!("BOT_COLOR: {e}"));
+                DEFAULT_COLOR
+            }),
+            None => match file.get("color") {
+                Some(color) => parse_color(color).unwrap_or_else(|e| {
+                    problems.push(format!("color: {e}"));
+                    DEFAULT_COLOR
+                }),
+                None => DEFAULT_COLOR,
+            },
+        };
+
+        let locale = env("BOT_LOCALE")
+            .or_else(|| field(file, "locale", &mut problems))
+            .unwrap_or_else(|| String::from(LOCALES[0]));
+        if let Err(e) = check_locale(&locale) {
+            problems.push(format!("locale: {e}"));
+        }
+
+        let bridge_channel = id_setting(
+            &env,
+            file,
+            ("DISCORD_CHANNEL_ID", "bridge_channel_id"),
+            &mut problems,
+        )
+        .map(ChannelId::new);
+
+        let twitch_channel = env("TWITCH_CHANNEL")
+            .or_else(|| field::<Option<String>>(file, "twitch_channel", &mut problems).flatten())
+            .filter(|channel| !channel.is_empty())
+            .and_then(|channel| {
+                twitch_channel_name(&channel)
+                    .map_err(|e| problems.push(format!("twitch_channel: {e}")))
+                    .ok()
+            });
+
+        let redis_url = env("REDIS_URL")
+            .or_else(|| field(file, "redis_url", &mut problems))
+            .unwrap_or_else(|| DEFAULT_REDIS_URL.into());
+        if let Err(e) = redis::Client::open(redis_url.as_str()) {
+            problems.push(format!("REDIS_URL: {e}"));
+        }
+
+        let role_sync: RoleSyncConfig = field(file, "role_sync", &mut problems).unwrap_or_default();
+        if role_sync.grace_period_hours < 0 {
+            problems.push(String::from(
+                "role_sync.grace_period_hours: must not be negative",
+            ));
+        }
+
+        let duels: DuelsConfig = field(file, "duels", &mut problems).unwrap_or_default();
+        if duels.accept_timeout_secs == 0 {
+            problems.push(String::from("duels.accept_timeout_secs: must be positive"));
+        }
+        if duels.turn_timeout_secs == 0 {
+            problems.push(String::from("duels.turn_timeout_secs: must be positive"));
+        }
+
+        let economy: EconomyConfig = field(file, "economy", &mut problems).unwrap_or_default();
+
+        let twitch_mod_role = id_setting(
+            &env,
+            file,
+            ("TWITCH_MOD_ROLE", "twitch_mod_role"),
+            &mut problems,
+        )
+        .map(RoleId::new);
+
+        let metrics_addr = env("METRICS_ADDR")
+            .map(|addr| ("METRICS_ADDR", addr))
+            .or_else(|| {
+                field::<Option<String>>(file, "metrics_addr", &mut problems)
+                    .flatten()
+                    .map(|addr| ("metrics_addr", addr))
+            })
+            .filter(|(_, addr)| !addr.is_empty())
+            .and_then(|(key, addr)| {
+                addr.parse()
+                    .map_err(|e| problems.push(format!("{key}: `{addr}` {e}")))
+                    .ok()
+            });
+
+        let test_guild = id_setting(
+            &env,
+            file,
+            ("DISCORD_TEST_GUILD", "test_guild_id"),
+            &mut problems,
+        )
+        .map(GuildId::new);
+
+        let templates = templates(
+            field::<Map<String, Value>>(file, "templates", &mut problems).unwrap_or_default(),
+            &mut problems,
+        );
+
+        let mut disabled_events = BTreeSet::new();
+        for (event, enabled) in
+            field::<HashMap<String, bool>>(file, "events", &mut problems).unwrap_or_default()
+        {
+            if !EVENTS.contains(&event.as_str()) {
+                problems.push(format!("events.{event}: unknown event"));
+            } else if !enabled {
+                disabled_events.insert(event);
+            }
+        }
+
+        let mut disabled_features = BTreeSet::new();
+        for (feature, enabled) in
+            field::<HashMap<String, bool>>(file, "features", &mut problems).unwrap_or_default()
+        {
+            if !FEATURES.contains(&feature.as_str()) {
+                problems.push(format!("features.{feature}: unknown feature"));
+            } else if !enabled {
+                disabled_features.insert(feature);
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(invalid(problems));
+        }
+
+        Ok(Self {
+            discord_token,
+            discord_prefix,
+            color,
+            locale,
+            bridge_channel,
+            twitch_channel,
+            redis_url,
+            role_sync,
+            duels,
+            economy,
+            twitch_mod_role,
+            metrics_addr,
+            test_guild,
+            templates,
+            disabled_events,
+            disabled_features,
+        })
+    }
+
+    pub fn event_enabled(&self, event_type: &str) -> bool {
+        !self.disabled_events.contains(event_type)
+    }
+
+    pub fn feature_enabled(&self, feature: &str) -> bool {
+        !self.disabled_features.contains(feature)
+    }
+
+    /// Whether events from the Twitch channel `channel` are relayed to the bridge channel.
+    pub fn bridges(&self, channel: &str) -> bool {
+        self.bridge_channel.is_some()
+            && self.feature_enabled("bridge")
+            && match (&self.twitch_channel, twitch_channel_name(channel)) {
+                (None, _) => true,
+                (Some(twitch_channel), Ok(channel)) => *twitch_channel == channel,
+                (Some(_), Err(_)) => false,
+            }
+    }
+
+    /// Copy of the configuration with a guild's overrides applied on top.
+    ///
+    /// Overrides that are no longer valid are skipped and returned as problems.
+    pub fn with_overrides(&self, overrides: &HashMap<String, String>) -> (Config, Vec<String>) {
+        let mut config = self.clone();
+        let mut problems = vec![];
+        for (key, value) in overrides {
+            if let Err(e) = config.apply(key, value) {
+                problems.push(format!("{key}: {e}"));
+            }
+        }
+        (config, problems)
+    }
+
+    /// Changes one of the settings guilds can override, see `GuildSettings`.
+    pub fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if let Some(event) = key.strip_prefix("event.") {
+            if !EVENTS.contains(&event) {
+                return Err(format!("unknown event `{event}`"));
+            }
+            match value {
+                "true" => self.disabled_events.remove(event),
+                "false" => self.disabled_events.insert(event.into()),
+                _ => return Err(format!("`{value}` is not `true` or `false`")),
+            };
+            return Ok(());
+        }
+
+        if let Some(feature) = key.strip_prefix("feature.") {
+            if !FEATURES.contains(&feature) {
+                return Err(format!("unknown feature `{feature}`"));
+            }
+            match value {
+                "true" => self.disabled_features.remove(feature),
+                "false" => self.disabled_features.insert(feature.into()),
+                _ => return Err(format!("`{value}` is not `true` or `false`")),
+            };
+            return Ok(());
+        }
+
+        if let Some(name) = key.strip_prefix("template.") {
+            let (_, _, fields) = TEMPLATES
+                .iter()
+                .find(|(template, _, _)| *template == name)
+                .ok_or_else(|| format!("unknown template `{name}`"))?;
+            let template = Template::compile(value, fields)?;
+            if let Some(current) = self.templates.get_mut(name) {
+                *current = template;
+            }
+            return Ok(());
+        }
+
+        match key {
+            "prefix" if value.trim().is_empty() => return Err(String::from("must not be empty")),
+            "prefix" => self.discord_prefix = value.into(),
+            "color" => self.color = parse_hex_color(value)?,
+            "bridge_channel" => match value.trim().parse::<u64>() {
+                Ok(id) if id != 0 => self.bridge_channel = Some(ChannelId::new(id)),
+                _ => return Err(format!("`{value}` is not a Discord ID")),
+            },
+            "twitch_channel" => self.twitch_channel = Some(twitch_channel_name(value)?),
+            "season_days" => {
+                self.duels.season_days = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("`{value}` is not a number of days"))?
+            }
+            "betting_turns" => {
+                self.duels.betting_turns = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("`{value}` is not a number of turns"))?
+            }
+            _ => return Err(format!("unknown setting `{key}`")),
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Human readable list of the settings that differ between `self` and `new`.
+    pub fn diff(&self, new: &Config) -> Vec<String> {
+        let mut changes = vec![];
+        if self.discord_token != new.discord_token {
+            changes.push(String::from("discord_token: (hidden)"));
+        }
+        let mut changed = |name: &str, old: String, new: String| {
+            if old != new {
+                changes.push(format!("{name}: {old} → {new}"));
+            }
+        };
+
+        changed(
+            "discord_prefix",
+            format!("`{}`", self.discord_prefix),
+            format!("`{}`", new.discord_prefix),
+        );
+        changed(
+            "color",
+            format!("{:?}", self.color),
+            format!("{:?}", new.color),
+        );
+        changed("locale", self.locale.clone(), new.locale.clone());
+        changed(
+            "bridge_channel",
+            format!("{:?}", self.bridge_channel),
+            format!("{:?}", new.bridge_channel),
+        );
+        changed(
+            "twitch_channel",
+            format!("{:?}", self.twitch_channel),
+            format!("{:?}", new.twitch_channel),
+        );
+        changed("redis_url", self.redis_url.clone(), new.redis_url.clone());
+        changed(
+            "role_sync",
+            format!("{:?}", self.role_sync),
+            format!("{:?}", new.role_sync),
+        );
+        changed(
+            "duels",
+            format!("{:?}", self.duels),
+            format!("{:?}", new.duels),
+        );
+        changed(
+            "economy",
+            format!("{:?}", self.economy),
+            format!("{:?}", new.economy),
+        );
+        changed(
+            "twitch_mod_role",
+            format!("{:?}", self.twitch_mod_role),
+            format!("{:?}", new.twitch_mod_role),
+        );
+        changed(
+            "metrics_addr",
+            format!("{:?}", self.metrics_addr),
+            format!("{:?}", new.metrics_addr),
+        );
+        changed(
+            "test_guild",
+            format!("{:?}", self.test_guild),
+            format!("{:?}", new.test_guild),
+        );
+        let templates = |t: &Templates| {
+            [
+                ("message", t.message.to_string()),
+                ("cheer", t.cheer.to_string()),
+                ("raided", t.raided.to_string()),
+                ("resub", t.resub.to_string()),
+                ("subgift", t.subgift.to_string()),
+                ("subscription", t.subscription.to_string()),
+                ("anon_gift", t.anon_gift.to_string()),
+            ]
+        };
+        for ((name, old), (_, new)) in templates(&self.templates)
+            .into_iter()
+            .zip(templates(&new.templates))
+        {
+            changed(&format!("templates.{name}"), old, new);
+        }
+        changed(
+            "disabled_events",
+            format!("{:?}", self.disabled_events),
+            format!("{:?}", new.disabled_events),
+        );
+        changed(
+            "disabled_features",
+            format!("{:?}", self.disabled_features),
+            format!("{:?}", new.disabled_features),
+        );
+
+        changes
+    }
+
+    /// Settings only read at startup.
+    fn needs_restart(&self, new: &Config) -> bool {
+        self.discord_token != new.discord_token
+            || self.redis_url != new.redis_url
+            || self.metrics_addr != new.metrics_addr
+            || self.test_guild != new.test_guild
+    }
+}
+
+/// The current configuration, swapped as a whole when the file is reloaded.
+///
+/// Readers take a snapshot with `get` and keep using it until they are done,
+/// so a reload never mixes old and new settings within one command or event.
+#[derive(Clone)]
+pub struct SharedConfig(Arc<RwLock<Arc<Config>>>);
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn swap(&self, config: Config) -> Arc<Config> {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, Arc::new(config))
+    }
+
+    /// Re-reads `path`, keeping the current configuration if the new one is invalid.
+    pub fn reload(&self, path: &Path) {
+        let new = match Config::load(path) {
+            Ok(new) => new,
+            Err(e) => {
+                tracing::error!("Keeping the current configuration. {e}");
+                return;
+            }
+        };
+
+        let changes = self.get().diff(&new);
+        if changes.is_empty() {
+            tracing::debug!("Configuration reloaded, nothing changed");
+            return;
+        }
+
+        let old = self.swap(new);
+        for change in &changes {
+            tracing::info!(change = %change, "Configuration changed");
+        }
+        if old.needs_restart(&self.get()) {
+            tracing::warn!(
+                "The Discord token, Redis URL, metrics address and test guild only change after a restart"
+            );
+        }
+    }
+}
+
+/// Reloads the configuration when `path` changes or the process receives SIGHUP.
+pub async fn watch(path: PathBuf, config: SharedConfig) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let file_tx = tx.clone();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && (event.kind.is_modify() || event.kind.is_create())
+            && event
+                .paths
+                .iter()
+                .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+        {
+            let _ = file_tx.send(());
+        }
+    });
+    // Watch the directory, editors and `docker cp` replace the file instead of writing to it.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let _watcher = match watcher {
+        Ok(mut watcher) => match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => Some(watcher),
+            Err(e) => {
+                tracing::warn!(error = %e, "Can't watch the configuration file, use SIGHUP to reload");
+                None
+            }
+        },
+        Err(e) => {
+            tracing::warn!(error = %e, "Can't watch the configuration file, use SIGHUP to reload");
+            None
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangup) => {
+                tokio::spawn(async move {
+                    while hangup.recv().await.is_some() {
+                        tracing::info!("Received SIGHUP");
+                        if tx.send(()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            Err(e) => tracing::warn!(error = %e, "Can't listen for SIGHUP"),
+        }
+    }
+
+    tracing::info!(path = %path.display(), "Watching the configuration for changes");
+
+    while rx.recv().await.is_some() {
+        tokio::time::sleep(RELOAD_DEBOUNCE).await;
+        while rx.try_recv().is_ok() {}
+        config.reload(&path);
+    }
+}
+
+fn invalid(problems: Vec<String>) -> Error {
+    Error::Config(format!(
+        "{} problem(s) found:\n{}",
+        problems.len(),
+        problems
+            .iter()
+            .map(|p| format!("  - {p}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
+}
+
+/// Reads an optional key of the file, reporting it if it has the wrong type.
+fn field<T: DeserializeOwned>(
+    file: &Map<String, Value>,
+    key: &str,
+    problems: &mut Vec<String>,
+) -> Option<T> {
+    let value = file.get(key)?;
+    serde_json::from_value(value.clone())
+        .map_err(|e| problems.push(format!("{key}: {e}")))
+        .ok()
+}
+
+/// Reads a Discord ID from the environment, or from the file as a number or a string.
+fn id_setting(
+    env: &impl Fn(&str) -> Option<String>,
+    file: &Map<String, Value>,
+    (env_key, file_key): (&str, &str),
+    problems: &mut Vec<String>,
+) -> Option<u64> {
+    let (key, id) = match (env(env_key), file.get(file_key)) {
+        (Some(id), _) => (env_key, id),
+        (None, None | Some(Value::Null)) => return None,
+        (None, Some(Value::String(id))) => (file_key, id.clone()),
+        (None, Some(id)) => (file_key, id.to_string()),
+    };
+    match id.trim().parse::<u64>() {
+        Ok(0) => None,
+        Ok(id) => Some(id),
+        Err(_) => {
+            problems.push(format!("{key}: `{id}` is not a Discord ID"));
+            None
+        }
+    }
+}
+
+fn check_locale(locale: &str) -> Result<(), String> {
+    if LOCALES.contains(&locale) {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{locale}` is not one of {}",
+            LOCALES.map(|l| format!("`{l}`")).join(", ")
+        ))
+    }
+}
+
+/// Twitch login without the `#` tmi.js puts in front of channel names, lowercased.
+pub fn twitch_channel_name(channel: &str) -> Result<String, String> {
+    let name = channel.trim().trim_start_matches('#').to_lowercase();
+    if name.is_empty()
+        || name.len() > 25
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("`{channel}` is not a Twitch channel name"));
+    }
+    Ok(name)
+}
+
+fn parse_color(color: &Value) -> Result<(u8, u8, u8), String> {
+    match color {
+        Value::String(hex) => parse_hex_color(hex),
+        Value::Array(rgb) if rgb.len() == 3 => {
+            let mut channels = [0u8; 3];
+            for (channel, value) in channels.iter_mut().zip(rgb) {
+                *channel = value
+                    .as_u64()
+                    .and_then(|v| u8::try_from(v).ok())
+                    .ok_or_else(|| format!("`{value}` is not between 0 and 255"))?;
+            }
+            Ok((channels[0], channels[1], channels[2]))
+        }
+        _ => Err(String::from(
+            "expected `[r, g, b]` or a `#rrggbb` hex string",
+        )),
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8), String> {
+    let digits = hex.trim().trim_start_matches('#');
+    match u32::from_str_radix(digits, 16) {
+        Ok(rgb) if digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        }
+        _ => Err(format!("`{hex}` is not a `#rrggbb` colour")),
+    }
+}
+
+fn templates(mut overrides: Map<String, Value>, problems: &mut Vec<String>) -> Templates {
+    let compiled = TEMPLATES.map(|(name, default, fields)| {
+        let source = match overrides.remove(name) {
+            None => default.to_string(),
+            Some(Value::String(source)) => source,
+            Some(_) => {
+                problems.push(format!("templates.{name}: expected a string"));
+                default.to_string()
+            }
+        };
+        Template::compile(&source, fields).unwrap_or_else(|e| {
+            problems.push(format!("templates.{name}: {e}"));
+            Template::compile(default, fields).expect("default templates are valid")
+        })
+    });
+    for name in overrides.keys() {
+        problems.push(format!("templates.{name}: unknown template"));
+    }
+
+    let [
+        message,
+        cheer,
+        raided,
+        resub,
+        subgift,
+        subscription,
+        anon_gift,
+    ] = compiled;
+    Templates {
+        message,
+        cheer,
+        raided,
+        resub,
+        subgift,
+        subscription,
+        anon_gift,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn env(vars: &[(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars.to_vec();
+        move |key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    fn file(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(file) => file,
+            _ => unreachable!(),
+        }
+    }
+
+    fn problems(file: &Map<String, Value>, env: impl Fn(&str) -> Option<String>) -> String {
+        match Config::from_sources(file, env) {
+            Err(Error::Config(problems)) => problems,
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("the configuration should be invalid"),
+        }
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert_eq!(parse_hex_color("#1e90ff"), Ok((0x1e, 0x90, 0xff)));
+        assert_eq!(parse_hex_color(" 000000 "), Ok((0, 0, 0)));
+        for hex in ["+abcde", "#-abcde", "#fff", "#1e90ff0", "#ggggggg", ""] {
+            assert!(parse_hex_color(hex).is_err(), "{hex} was accepted");
+        }
+    }
+
+    #[test]
+    fn defaults_only_need_a_token() {
+        let config = Config::from_sources(&Map::new(), env(&[("DISCORD_BOT_TOKEN", "token")]))
+            .ok()
+            .unwrap();
+        assert_eq!(config.discord_token, "token");
+        assert_eq!(config.discord_prefix, DEFAULT_PREFIX);
+        assert_eq!(config.color, DEFAULT_COLOR);
+        assert_eq!(config.metrics_addr, None);
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let config = Config::from_sources(
+            &file(json!({ "discord_prefix": "?", "color": [1, 2, 3] })),
+            env(&[
+                ("DISCORD_BOT_TOKEN", "token"),
+                ("DISCORD_PREFIX", "!"),
+                ("BOT_COLOR", "#0a0b0c"),
+            ]),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(config.discord_prefix, "!");
+        assert_eq!(config.color, (10, 11, 12));
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let report = problems(
+            &file(json!({
+                "discord_prefix": " ",
+                "color": [1, 2, 300],
+                "duels": { "turn_timeout_secs": 0 },
+                "templates": { "cheer": "{user} cheered {amount}", "farewell": "bye" },
+                "events": { "hosted": false },
+            })),
+            env(&[("BOT_COLOR", "+abcde")]),
+        );
+        for problem in [
+            "DISCORD_BOT_TOKEN: missing",
+            "discord_prefix: must not be empty",
+            "BOT_COLOR: `+abcde` is not a `#rrggbb` colour",
+            "duels.turn_timeout_secs: must be positive",
+            "templates.cheer: unknown placeholder `{amount}`",
+            "templates.farewell: unknown template",
+            "events.hosted: unknown event",
+        ] {
+            assert!(
+                report.contains(problem),
+                "{problem} missing from:\n{report}"
+            );
+        }
+        assert!(report.starts_with("7 problem(s) found:"), "{report}");
+    }
+
+    #[test]
+    fn reports_the_file_colour_without_an_override() {
+        let report = problems(&file(json!({ "color": [1, 2, 300] })), env(&[]));
+        assert!(
+            report.contains("color: `300` is not between 0 and 255"),
+            "{report}"
+        );
+    }
+}
@@ -0,0 +1,607 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use poise::serenity_prelude::{self as serenity, CreateEmbed, EditMessage, MessageId, UserId};
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    Error, GameState,
+    bets::{Bets, Pools},
+    guild_settings::GuildSettings,
+    profiles::{LOSS_XP, Profiles, WIN_XP},
+    ratings::Ratings,
+    wallets::{CURRENCY, HoldError, Wallets},
+};
+use engine::DuelError;
+
+pub mod engine;
+
+/// Duels in progress, JSON `GameState` by message id.
+const DUELS_KEY: &str = "duels:active";
+/// Message ids of the duels in progress, scored by the time they time out.
+const DEADLINES_KEY: &str = "duels:deadlines";
+const TIMEOUT_POLL: Duration = Duration::from_secs(2);
+/// Timed out duels handled per poll.
+const TIMEOUT_BATCH: isize = 50;
+/// Duels that ended and weren't fully settled yet, JSON `Settlement` by message id.
+const SETTLING_KEY: &str = "duels:settling";
+/// Time before a settlement step that failed, or never ran, is tried again.
+const SETTLE_RETRY_SECS: i64 = 60;
+
+/// Custom ids of the buttons on duel messages.
+const BUTTONS: [&str; 7] = [
+    "accept_duel",
+    "cancel_duel",
+    "attack_action",
+    "defend_action",
+    "heal_action",
+    "special_action",
+    "place_bet",
+];
+
+/// Splits the custom id of a duel button into the button and the turn it was shown on.
+///
+/// Combat buttons are suffixed with the turn, like `attack_action:4`, so a click on a
+/// previous turn's buttons is told apart from a new move.
+pub fn parse_button(custom_id: &str) -> Option<(&str, Option<u32>)> {
+    let (button, turn) = match custom_id.split_once(':') {
+        Some((button, turn)) => (button, Some(turn.parse().ok()?)),
+        None => (custom_id, None),
+    };
+    BUTTONS.contains(&button).then_some((button, turn))
+}
+
+fn default_accept_timeout_secs() -> u64 {
+    300
+}
+
+fn default_turn_timeout_secs() -> u64 {
+    120
+}
+
+fn default_betting_turns() -> u32 {
+    2
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DuelsConfig {
+    /// Time the challenged user has to accept.
+    #[serde(default = "default_accept_timeout_secs")]
+    pub accept_timeout_secs: u64,
+    /// Time a player has to act before forfeiting.
+    #[serde(default = "default_turn_timeout_secs")]
+    pub turn_timeout_secs: u64,
+    /// Length of a ranked season, seasons only end with `/leaderboard reset` when 0.
+    #[serde(default)]
+    pub season_days: u64,
+    /// Turns spectators can bet during, 0 turns betting off.
+    #[serde(default = "default_betting_turns")]
+    pub betting_turns: u32,
+}
+
+impl Default for DuelsConfig {
+    fn default() -> Self {
+        Self {
+            accept_timeout_secs: default_accept_timeout_secs(),
+            turn_timeout_secs: default_turn_timeout_secs(),
+            season_days: 0,
+            betting_turns: default_betting_turns(),
+        }
+    }
+}
+
+impl DuelsConfig {
+    /// When a challenge sent now expires.
+    pub fn accept_deadline(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.accept_timeout_secs as i64
+    }
+
+    /// When a turn starting now is forfeited.
+    pub fn turn_deadline(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.turn_timeout_secs as i64
+    }
+
+    /// Whether spectators can still bet on `duel`.
+    pub fn bets_open(&self, duel: &engine::Duel) -> bool {
+        duel.phase == engine::Phase::Active && duel.turns() < self.betting_turns
+    }
+}
+
+/// Duels in progress, kept in memory and written through to Redis so they survive restarts.
+///
+/// Each duel has its own lock so a slow Discord call in one duel doesn't hold up the others.
+#[derive(Clone)]
+pub struct Duels {
+    con: ConnectionManager,
+    active: Arc<Mutex<HashMap<MessageId, Arc<Mutex<GameState>>>>>,
+}
+
+/// Result of changing a duel with [`Duels::update`].
+pub enum Transition<T, E = DuelError> {
+    /// No duel in progress on this message.
+    Missing,
+    /// The change wasn't legal, the duel is untouched.
+    Rejected(E),
+    /// The duel as it is after the change.
+    Applied(GameState, T),
+}
+
+/// Part of settling a duel that ended, see [`DuelResults::settle`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Step {
+    Profiles,
+    Rating,
+    Wager,
+    Bets,
+}
+
+/// A duel that ended and the steps it still needs to be settled.
+#[derive(Serialize, Deserialize, Debug)]
+struct Settlement {
+    state: GameState,
+    pending: Vec<Step>,
+    /// Unix time the pending steps are retried.
+    retry_at: i64,
+}
+
+impl Settlement {
+    fn new(state: GameState) -> Self {
+        let pending = match state.duel.phase {
+            engine::Phase::Finished { .. } => {
+                vec![Step::Profiles, Step::Rating, Step::Wager, Step::Bets]
+            }
+            // The coins at stake go back.
+            _ => vec![Step::Wager, Step::Bets],
+        };
+        Self {
+            state,
+            pending,
+            retry_at: chrono::Utc::now().timestamp() + SETTLE_RETRY_SECS,
+        }
+    }
+}
+
+impl Duels {
+    /// Loads the duels saved by the previous runs, skipping the ones that can't be read.
+    pub async fn load(con: ConnectionManager) -> RedisResult<Self> {
+        let mut redis = con.clone();
+        let entries: HashMap<u64, String> = redis.hgetall(DUELS_KEY).await?;
+
+        let mut active = HashMap::new();
+        for (id, state) in entries {
+            match serde_json::from_str(&state) {
+                Ok(state) => {
+                    active.insert(MessageId::new(id), Arc::new(Mutex::new(state)));
+                }
+                Err(e) => {
+                    tracing::warn!(message_id = id, error = %e, "Dropping unreadable duel");
+                    let _: () = redis.hdel(DUELS_KEY, id).await?;
+                    let _: () = redis.zrem(DEADLINES_KEY, id).await?;
+                }
+            }
+        }
+
+        Ok(Self {
+            con,
+            active: Arc::new(Mutex::new(active)),
+        })
+    }
+
+    pub async fn count(&self) -> usize {
+        self.active.lock().await.len()
+    }
+
+    async fn get(&self, message_id: MessageId) -> Option<Arc<Mutex<GameState>>> {
+        self.active.lock().await.get(&message_id).cloned()
+    }
+
+    /// A copy of the duel posted in `message_id`, it may change before it's used.
+    pub async fn peek(&self, message_id: MessageId) -> Option<GameState> {
+        let duel = self.get(message_id).await?;
+        Some(duel.lock().await.clone())
+    }
+
+    /// Saves a new duel and schedules its deadline.
+    pub async fn insert(&self, message_id: MessageId, state: GameState) -> RedisResult<()> {
+        self.save(message_id, &state).await?;
+        self.active
+            .lock()
+            .await
+            .insert(message_id, Arc::new(Mutex::new(state)));
+        Ok(())
+    }
+
+    /// Applies `change` to the duel posted in `message_id`.
+    ///
+    /// The duel stays locked only while the change is applied and saved, and a duel that
+    /// ended is forgotten. Clicks waiting on the lock then see the ended duel and are rejected.
+    pub async fn update<T>(
+        &self,
+        message_id: MessageId,
+        change: impl FnOnce(&mut GameState) -> Result<T, DuelError>,
+    ) -> RedisResult<Transition<T>> {
+        self.update_with(message_id, async |state| Ok(change(state)))
+            .await
+    }
+
+    /// Like [`Duels::update`], for changes that also write to Redis while the duel is locked.
+    ///
+    /// The duel is untouched when `change` fails or rejects.
+    pub async fn update_with<T, E>(
+        &self,
+        message_id: MessageId,
+        change: impl AsyncFnOnce(&mut GameState) -> RedisResult<Result<T, E>>,
+    ) -> RedisResult<Transition<T, E>> {
+        let Some(duel) = self.get(message_id).await else {
+            return Ok(Transition::Missing);
+        };
+        let mut current = duel.lock().await;
+
+        let mut state = current.clone();
+        let outcome = match change(&mut state).await? {
+            Ok(outcome) => outcome,
+            Err(e) => return Ok(Transition::Rejected(e)),
+        };
+
+        if state.duel.phase.is_over() {
+            self.remove(message_id, &state).await?;
+        } else {
+            self.save(message_id, &state).await?;
+        }
+        *current = state.clone();
+        Ok(Transition::Applied(state, outcome))
+    }
+
+    async fn save(&self, message_id: MessageId, state: &GameState) -> RedisResult<()> {
+        let payload = serde_json::to_string(state).unwrap_or_default();
+        let _: () = redis::pipe()
+            .atomic()
+            .hset(DUELS_KEY, message_id.get(), payload)
+            .zadd(DEADLINES_KEY, message_id.get(), state.deadline)
+            .query_async(&mut self.con.clone())
+            .await?;
+        Ok(())
+    }
+
+    /// Forgets a duel that ended, it stays queued until it's settled.
+    async fn remove(&self, message_id: MessageId, state: &GameState) -> RedisResult<()> {
+        let settlement = serde_json::to_string(&Settlement::new(state.clone())).unwrap_or_default();
+        let _: () = redis::pipe()
+            .atomic()
+            .hdel(DUELS_KEY, message_id.get())
+            .zrem(DEADLINES_KEY, message_id.get())
+            .hset(SETTLING_KEY, message_id.get(), settlement)
+            .query_async(&mut self.con.clone())
+            .await?;
+        self.active.lock().await.remove(&message_id);
+        Ok(())
+    }
+
+    /// Ends the duels whose deadline passed: challenges expire and idle players forfeit.
+    async fn time_out(
+        &self,
+        http_client: &serenity::Http,
+        results: &DuelResults,
+    ) -> Result<(), Error> {
+        let now = chrono::Utc::now().timestamp();
+        let mut con = self.con.clone();
+        let due: Vec<u64> = con
+            .zrangebyscore_limit(DEADLINES_KEY, 0, now, 0, TIMEOUT_BATCH)
+            .await?;
+
+        for id in due {
+            let message_id = MessageId::new(id);
+            let transition = self
+                .update(message_id, |state| {
+                    // Played after the deadline was read.
+                    if state.deadline > now {
+                        return Err(DuelError::AlreadyPlayed);
+                    }
+                    Ok(state.duel.time_out())
+                })
+                .await?;
+            let (state, event) = match transition {
+                Transition::Applied(state, event) => (state, event),
+                Transition::Rejected(_) => continue,
+                Transition::Missing => {
+                    let _: () = con.zrem(DEADLINES_KEY, id).await?;
+                    continue;
+                }
+            };
+
+            let embed = match (event, state.duel.phase) {
+                (Some(engine::Event::Forfeit { player }), engine::Phase::Finished { winner }) => {
+                    tracing::info!(message_id = id, loser = player, "Duel forfeited");
+                    let results = results.settle(message_id, &state).await;
+                    CreateEmbed::new()
+                        .title("Duel Forfeited")
+                        .description(format!(
+                            "<@{player}> didn't act in time and forfeits. <@{winner}> wins!\n\n{}",
+                            results.join("\n")
+                        ))
+                }
+                _ => {
+                    tracing::info!(message_id = id, "Duel challenge expired");
+                    results.settle(message_id, &state).await;
+                    CreateEmbed::new()
+                        .title("Duel Expired")
+                        .description(format!(
+                            "<@{}> didn't answer the challenge of <@{}> in time.",
+                            state.duel.opponent(),
+                            state.duel.challenger()
+                        ))
+                }
+            };
+
+            let edit = EditMessage::new()
+                .embed(embed.color(serenity::Colour::from_rgb(0, 100, 255)))
+                .components(vec![]);
+            if let Err(e) = state
+                .channel_id
+                .edit_message(http_client, message_id, edit)
+                .await
+            {
+                tracing::warn!(message_id = id, error = %e, "Failed to update timed out duel");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records what a won duel changes: the players' profiles, their rating on the server,
+/// the coins they wagered and the bets of spectators.
+#[derive(Clone)]
+pub struct DuelResults {
+    con: ConnectionManager,
+    profiles: Profiles,
+    ratings: Ratings,
+    wallets: Wallets,
+    bets: Bets,
+    settings: GuildSettings,
+}
+
+impl DuelResults {
+    pub fn new(
+        con: ConnectionManager,
+        profiles: Profiles,
+        ratings: Ratings,
+        wallets: Wallets,
+        bets: Bets,
+        settings: GuildSettings,
+    ) -> Self {
+        Self {
+            con,
+            profiles,
+            ratings,
+            wallets,
+            bets,
+            settings,
+        }
+    }
+
+    /// Settles a duel that ended and returns the lines announcing it.
+    ///
+    /// A step that fails is logged and left to [`DuelResults::retry`], the others still run.
+    pub async fn settle(&self, message_id: MessageId, state: &GameState) -> Vec<String> {
+        let settlement = Settlement::new(state.clone());
+        self.run(message_id, settlement).await
+    }
+
+    /// Runs the steps settlements still need, once their retry time came.
+    async fn retry(&self) -> RedisResult<()> {
+        let mut con = self.con.clone();
+        let entries: HashMap<u64, String> = con.hgetall(SETTLING_KEY).await?;
+        let now = chrono::Utc::now().timestamp();
+
+        for (id, settlement) in entries {
+            let settlement: Settlement = match serde_json::from_str(&settlement) {
+                Ok(settlement) => settlement,
+                Err(e) => {
+                    tracing::warn!(message_id = id, error = %e, "Dropping unreadable settlement");
+                    let _: () = con.hdel(SETTLING_KEY, id).await?;
+                    continue;
+                }
+            };
+            if settlement.retry_at <= now {
+                tracing::info!(message_id = id, steps = ?settlement.pending, "Retrying duel settlement");
+                self.run(MessageId::new(id), settlement).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn run(&self, message_id: MessageId, mut settlement: Settlement) -> Vec<String> {
+        let mut lines = vec![];
+        let mut failed = vec![];
+        for step in settlement.pending {
+            match self.step(step, message_id, &settlement.state).await {
+                Ok(step_lines) => lines.extend(step_lines),
+                Err(e) => {
+                    tracing::error!(message_id = %message_id, ?step, error = %e, "Failed to settle duel");
+                    failed.push(step);
+                }
+            }
+        }
+
+        settlement.pending = failed;
+        settlement.retry_at = chrono::Utc::now().timestamp() + SETTLE_RETRY_SECS;
+        let mut con = self.con.clone();
+        let saved: RedisResult<()> = if settlement.pending.is_empty() {
+            con.hdel(SETTLING_KEY, message_id.get()).await
+        } else {
+            let payload = serde_json::to_string(&settlement).unwrap_or_default();
+            con.hset(SETTLING_KEY, message_id.get(), payload).await
+        };
+        if let Err(e) = saved {
+            tracing::error!(message_id = %message_id, error = %e, "Failed to save duel settlement");
+        }
+        lines
+    }
+
+    async fn step(
+        &self,
+        step: Step,
+        message_id: MessageId,
+        state: &GameState,
+    ) -> Result<Vec<String>, Error> {
+        let duel = &state.duel;
+        let players = [duel.challenger(), duel.opponent()].map(UserId::new);
+        let winner = match duel.phase {
+            engine::Phase::Finished { winner } => Some(UserId::new(winner)),
+            _ => None,
+        };
+        let loser = winner.map(|winner| {
+            if winner == players[0] {
+                players[1]
+            } else {
+                players[0]
+            }
+        });
+
+        match (step, state.guild_id, winner.zip(loser)) {
+            (Step::Profiles, _, Some((winner, loser))) => {
+                let forfeit = matches!(duel.log.last(), Some(engine::Event::Forfeit { .. }));
+                let level_ups = self.profiles.record_duel(winner, loser, forfeit).await?;
+
+                let loss_xp = if forfeit { 0 } else { LOSS_XP };
+                let mut lines = vec![format!(
+                    "✨ <@{winner}> +{WIN_XP} XP, <@{loser}> +{loss_xp} XP"
+                )];
+                lines.extend(level_ups.into_iter().map(|level_up| {
+                    format!(
+                        "🎉 <@{}> reached level {}!",
+                        level_up.user_id, level_up.level
+                    )
+                }));
+                Ok(lines)
+            }
+            (Step::Rating, Some(guild_id), Some((winner, loser))) => {
+                let season_days = self.settings.config(Some(guild_id)).await.duels.season_days;
+                let season = self.ratings.season(guild_id, season_days).await?;
+                let changes = self
+                    .ratings
+                    .record_duel(guild_id, &season, winner, loser)
+                    .await?;
+                Ok(vec![format!(
+                    "📈 {}",
+                    changes
+                        .iter()
+                        .map(|change| format!(
+                            "<@{}> **{}** ({:+})",
+                            change.user_id, change.rating, change.delta
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )])
+            }
+            (Step::Wager, Some(guild_id), Some((winner, _))) => {
+                let stake = self
+                    .wallets
+                    .release(guild_id, message_id, &[(winner, 2)])
+                    .await?;
+                Ok(if stake > 0 {
+                    vec![format!("💰 <@{winner}> wins **{}** {CURRENCY}", stake * 2)]
+                } else {
+                    vec![]
+                })
+            }
+            // Nobody won, both players get their wager back.
+            (Step::Wager, Some(guild_id), None) => {
+                let stake = self
+                    .wallets
+                    .release(guild_id, message_id, &players.map(|id| (id, 1)))
+                    .await?;
+                if stake > 0 {
+                    tracing::info!(message_id = %message_id, stake, "Refunded duel wager");
+                }
+                Ok(vec![])
+            }
+            (Step::Bets, Some(guild_id), Some((winner, _))) => {
+                let side = usize::from(winner != players[0]);
+                let (pools, payouts) = self.bets.settle(guild_id, message_id, Some(side)).await?;
+                Ok(if pools.bettors[side] > 0 {
+                    vec![format!(
+                        "🎲 {} backer(s) of <@{winner}> share **{}** {CURRENCY}",
+                        pools.bettors[side],
+                        payouts.iter().map(|(_, amount)| amount).sum::<u64>()
+                    )]
+                } else if !payouts.is_empty() {
+                    vec![format!(
+                        "🎲 Nobody backed <@{winner}>, the bets were refunded"
+                    )]
+                } else {
+                    vec![]
+                })
+            }
+            (Step::Bets, Some(guild_id), None) => {
+                self.bets.settle(guild_id, message_id, None).await?;
+                Ok(vec![])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Lets spectators bet on the duel posted in `message_id`.
+    pub async fn open_bets(&self, message_id: MessageId) -> RedisResult<()> {
+        self.bets.open(message_id).await
+    }
+
+    pub async fn close_bets(&self, message_id: MessageId) -> RedisResult<()> {
+        self.bets.close(message_id).await
+    }
+
+    /// Coins spectators bet on the duel posted in `message_id` so far.
+    pub async fn bet_pools(&self, message_id: MessageId) -> RedisResult<Pools> {
+        self.bets.pools(message_id).await
+    }
+
+    /// Takes the wager of both players of the duel posted in `message_id`.
+    ///
+    /// Returns the player who can't cover it. Coins still held from an earlier attempt
+    /// that couldn't be saved are used as they are.
+    pub async fn hold_wager(
+        &self,
+        message_id: MessageId,
+        state: &GameState,
+    ) -> RedisResult<Result<(), UserId>> {
+        let Some(guild_id) = state.guild_id.filter(|_| state.wager > 0) else {
+            return Ok(Ok(()));
+        };
+        let players = [state.duel.challenger(), state.duel.opponent()].map(UserId::new);
+        Ok(
+            match self
+                .wallets
+                .hold(guild_id, message_id, state.wager, players)
+                .await?
+            {
+                Ok(()) | Err(HoldError::AlreadyHeld) => Ok(()),
+                Err(HoldError::Insufficient(user_id)) => Err(user_id),
+            },
+        )
+    }
+}
+
+/// Times duels out as their deadlines pass, deadlines are kept in Redis across restarts.
+pub async fn start_timeouts(
+    http_client: serenity::Http,
+    duels: Duels,
+    results: DuelResults,
+    token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(TIMEOUT_POLL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = token.cancelled() => return,
+        }
+        if let Err(e) = duels.time_out(&http_client, &results).await {
+            tracing::error!(error = %e, "Failed to time duels out");
+        }
+        if let Err(e) = results.retry().await {
+            tracing::error!(error = %e, "Failed to retry duel settlements");
+        }
+    }
+}
@@ -0,0 +1,686 @@
+//! Rules of duels, kept free of Discord so they can be unit tested.
+//!
+//! A duel goes from `Pending` (challenged) to `Active` (accepted) and ends `Finished`,
+//! `Cancelled` or `Expired`. Every change goes through a method that checks the move is
+//! legal, and randomness comes from the caller so tests can seed it.
+
+use std::{fmt, ops::RangeInclusive};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Discord user id of a player.
+pub type PlayerId = u64;
+
+/// HP of a level 1 player.
+pub const BASE_HP: u32 = 100;
+/// Charge needed for the special attack, attacking and defending add one each.
+pub const SPECIAL_CHARGE: u8 = 3;
+/// Own turns a player waits after healing.
+pub const HEAL_COOLDOWN: u8 = 3;
+/// Turns kept in the combat log.
+pub const LOG_LINES: usize = 5;
+
+const ATTACK_DAMAGE: RangeInclusive<u32> = 8..=14;
+const SPECIAL_DAMAGE: RangeInclusive<u32> = 22..=32;
+const HEAL_AMOUNT: RangeInclusive<u32> = 12..=20;
+const ATTACK_MISS_CHANCE: f64 = 0.1;
+const SPECIAL_MISS_CHANCE: f64 = 0.2;
+const CRIT_CHANCE: f64 = 0.15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Waiting for the opponent to accept.
+    Pending,
+    Active,
+    Finished {
+        winner: PlayerId,
+    },
+    Cancelled,
+    /// The challenge was never accepted.
+    Expired,
+}
+
+impl Phase {
+    /// Whether the duel ended and can be forgotten.
+    pub fn is_over(self) -> bool {
+        !matches!(self, Phase::Pending | Phase::Active)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Attack,
+    Defend,
+    Heal,
+    Special,
+}
+
+/// What happened during a turn.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Hit {
+        attacker: PlayerId,
+        defender: PlayerId,
+        damage: u32,
+        crit: bool,
+        /// The defender was guarding and took half damage.
+        guarded: bool,
+        special: bool,
+    },
+    Miss {
+        attacker: PlayerId,
+        defender: PlayerId,
+        special: bool,
+    },
+    Guard {
+        player: PlayerId,
+    },
+    Heal {
+        player: PlayerId,
+        amount: u32,
+    },
+    /// The player ran out of time on their turn.
+    Forfeit {
+        player: PlayerId,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuelError {
+    SelfDuel,
+    /// The duel was already accepted or is over.
+    NotPending,
+    /// The duel isn't being fought.
+    NotActive,
+    NotYourDuel,
+    NotYourTurn,
+    /// The click was for a turn that was already played.
+    AlreadyPlayed,
+    /// Healing is available again in this many turns.
+    OnCooldown(u8),
+    /// The special attack is charged this much.
+    NotCharged(u8),
+}
+
+impl fmt::Display for DuelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuelError::SelfDuel => write!(f, "You can't duel yourself!"),
+            DuelError::NotPending => write!(f, "This duel was already accepted."),
+            DuelError::NotActive => write!(f, "This duel isn't being fought."),
+            DuelError::NotYourDuel => write!(f, "This duel challenge is not for you!"),
+            DuelError::NotYourTurn => write!(f, "It's not your turn!"),
+            DuelError::AlreadyPlayed => write!(f, "This turn was already played."),
+            DuelError::OnCooldown(turns) => write!(f, "You can heal again in {turns} turn(s)."),
+            DuelError::NotCharged(charge) => write!(
+                f,
+                "Your special attack is charged {charge}/{SPECIAL_CHARGE}."
+            ),
+        }
+    }
+}
+
+/// What a player brings to a duel from their profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub max_hp: u32,
+    /// Added to the damage of every attack.
+    pub bonus_damage: u32,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            max_hp: BASE_HP,
+            bonus_damage: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Fighter {
+    pub id: PlayerId,
+    pub hp: u32,
+    pub max_hp: u32,
+    #[serde(default)]
+    pub bonus_damage: u32,
+    /// Takes half damage from the next hit.
+    pub defending: bool,
+    /// Charge built towards the special attack.
+    pub charge: u8,
+    /// Own turns left before healing is available again.
+    pub heal_cooldown: u8,
+}
+
+impl Fighter {
+    fn new(id: PlayerId, stats: Stats) -> Self {
+        Self {
+            id,
+            hp: stats.max_hp,
+            max_hp: stats.max_hp,
+            bonus_damage: stats.bonus_damage,
+            defending: false,
+            charge: 0,
+            heal_cooldown: 0,
+        }
+    }
+
+    /// Why this fighter can't take `action`, if they can't.
+    pub fn check(&self, action: Action) -> Result<(), DuelError> {
+        match action {
+            Action::Heal if self.heal_cooldown > 0 => {
+                Err(DuelError::OnCooldown(self.heal_cooldown))
+            }
+            Action::Special if self.charge < SPECIAL_CHARGE => {
+                Err(DuelError::NotCharged(self.charge))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn build_charge(&mut self) {
+        self.charge = (self.charge + 1).min(SPECIAL_CHARGE);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Duel {
+    /// The challenger first, they play the first turn.
+    pub fighters: [Fighter; 2],
+    pub phase: Phase,
+    /// Index in `fighters` of the player whose turn it is.
+    turn: usize,
+    /// Turns played so far.
+    #[serde(default)]
+    turns: u32,
+    /// Last turns of the duel, newest last.
+    pub log: Vec<Event>,
+}
+
+impl Duel {
+    /// A challenge between two players, `stats` in the same order.
+    pub fn challenge(
+        challenger: PlayerId,
+        opponent: PlayerId,
+        stats: [Stats; 2],
+    ) -> Result<Self, DuelError> {
+        if challenger == opponent {
+            return Err(DuelError::SelfDuel);
+        }
+        Ok(Self {
+            fighters: [
+                Fighter::new(challenger, stats[0]),
+                Fighter::new(opponent, stats[1]),
+            ],
+            phase: Phase::Pending,
+            turn: 0,
+            turns: 0,
+            log: Vec::new(),
+        })
+    }
+
+    pub fn challenger(&self) -> PlayerId {
+        self.fighters[0].id
+    }
+
+    pub fn opponent(&self) -> PlayerId {
+        self.fighters[1].id
+    }
+
+    /// The player whose turn it is.
+    pub fn turn(&self) -> &Fighter {
+        &self.fighters[self.turn]
+    }
+
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+
+    /// Rejects a move meant for another turn than the current one, like a repeated click.
+    pub fn expect_turn(&self, turn: u32) -> Result<(), DuelError> {
+        if turn != self.turns {
+            return Err(DuelError::AlreadyPlayed);
+        }
+        Ok(())
+    }
+
+    pub fn is_player(&self, player: PlayerId) -> bool {
+        self.fighters.iter().any(|fighter| fighter.id == player)
+    }
+
+    pub fn accept(&mut self, by: PlayerId) -> Result<(), DuelError> {
+        if self.phase != Phase::Pending {
+            return Err(DuelError::NotPending);
+        }
+        if by != self.opponent() {
+            return Err(DuelError::NotYourDuel);
+        }
+        self.phase = Phase::Active;
+        Ok(())
+    }
+
+    /// Withdraws or declines the challenge.
+    pub fn cancel(&mut self, by: PlayerId) -> Result<(), DuelError> {
+        if !self.is_player(by) {
+            return Err(DuelError::NotYourDuel);
+        }
+        if self.phase != Phase::Pending {
+            return Err(DuelError::NotPending);
+        }
+        self.phase = Phase::Cancelled;
+        Ok(())
+    }
+
+    /// Ends the duel once its deadline passed: a challenge expires, the player whose turn it
+    /// is forfeits a fight.
+    pub fn time_out(&mut self) -> Option<Event> {
+        match self.phase {
+            Phase::Pending => {
+                self.phase = Phase::Expired;
+                None
+            }
+            Phase::Active => {
+                let player = self.turn().id;
+                self.phase = Phase::Finished {
+                    winner: self.fighters[1 - self.turn].id,
+                };
+                Some(self.record(Event::Forfeit { player }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Plays `action` for `by` and passes the turn.
+    pub fn act(
+        &mut self,
+        by: PlayerId,
+        action: Action,
+        rng: &mut impl Rng,
+    ) -> Result<Event, DuelError> {
+        if self.phase != Phase::Active {
+            return Err(DuelError::NotActive);
+        }
+        if !self.is_player(by) {
+            return Err(DuelError::NotYourDuel);
+        }
+        if by != self.turn().id {
+            return Err(DuelError::NotYourTurn);
+        }
+        self.turn().check(action)?;
+
+        let [first, second] = &mut self.fighters;
+        let (attacker, defender) = if self.turn == 0 {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        attacker.defending = false;
+        let heal_cooldown = attacker.heal_cooldown.saturating_sub(1);
+
+        let event = match action {
+            Action::Attack | Action::Special => {
+                let special = action == Action::Special;
+                let (damage, miss_chance) = if special {
+                    attacker.charge = 0;
+                    (SPECIAL_DAMAGE, SPECIAL_MISS_CHANCE)
+                } else {
+                    attacker.build_charge();
+                    (ATTACK_DAMAGE, ATTACK_MISS_CHANCE)
+                };
+
+                if rng.random_bool(miss_chance) {
+                    Event::Miss {
+                        attacker: attacker.id,
+                        defender: defender.id,
+                        special,
+                    }
+                } else {
+                    let crit = rng.random_bool(CRIT_CHANCE);
+                    let mut damage = rng.random_range(damage) + attacker.bonus_damage;
+                    if crit {
+                        damage *= 2;
+                    }
+                    let guarded = defender.defending;
+                    if guarded {
+                        damage /= 2;
+                        defender.defending = false;
+                    }
+                    defender.hp = defender.hp.saturating_sub(damage);
+                    Event::Hit {
+                        attacker: attacker.id,
+                        defender: defender.id,
+                        damage,
+                        crit,
+                        guarded,
+                        special,
+                    }
+                }
+            }
+            Action::Defend => {
+                attacker.defending = true;
+                attacker.build_charge();
+                Event::Guard {
+                    player: attacker.id,
+                }
+            }
+            Action::Heal => {
+                let amount = rng
+                    .random_range(HEAL_AMOUNT)
+                    .min(attacker.max_hp - attacker.hp);
+                attacker.hp += amount;
+                Event::Heal {
+                    player: attacker.id,
+                    amount,
+                }
+            }
+        };
+
+        attacker.heal_cooldown = if action == Action::Heal {
+            HEAL_COOLDOWN
+        } else {
+            heal_cooldown
+        };
+        if defender.hp == 0 {
+            self.phase = Phase::Finished {
+                winner: attacker.id,
+            };
+        }
+
+        self.turn = 1 - self.turn;
+        self.turns += 1;
+        Ok(self.record(event))
+    }
+
+    fn record(&mut self, event: Event) -> Event {
+        self.log.push(event.clone());
+        if self.log.len() > LOG_LINES {
+            self.log.remove(0);
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    const ALICE: PlayerId = 1;
+    const BOB: PlayerId = 2;
+    const BASE: [Stats; 2] = [Stats {
+        max_hp: BASE_HP,
+        bonus_damage: 0,
+    }; 2];
+
+    fn active() -> Duel {
+        let mut duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        duel.accept(BOB).unwrap();
+        duel
+    }
+
+    #[test]
+    fn challenge_starts_pending() {
+        let duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        assert_eq!(duel.phase, Phase::Pending);
+        assert_eq!(duel.turn().id, ALICE);
+        assert!(duel.fighters.iter().all(|fighter| fighter.hp == BASE_HP));
+    }
+
+    #[test]
+    fn cant_duel_yourself() {
+        assert_eq!(
+            Duel::challenge(ALICE, ALICE, BASE),
+            Err(DuelError::SelfDuel)
+        );
+    }
+
+    #[test]
+    fn only_the_opponent_accepts() {
+        let mut duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        assert_eq!(duel.accept(ALICE), Err(DuelError::NotYourDuel));
+        assert_eq!(duel.accept(3), Err(DuelError::NotYourDuel));
+        duel.accept(BOB).unwrap();
+        assert_eq!(duel.phase, Phase::Active);
+        assert_eq!(duel.accept(BOB), Err(DuelError::NotPending));
+    }
+
+    #[test]
+    fn cancel_only_while_pending() {
+        let mut duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        assert_eq!(duel.cancel(3), Err(DuelError::NotYourDuel));
+        duel.cancel(BOB).unwrap();
+        assert_eq!(duel.phase, Phase::Cancelled);
+
+        let mut duel = active();
+        assert_eq!(duel.cancel(ALICE), Err(DuelError::NotPending));
+    }
+
+    #[test]
+    fn cant_act_before_accepting() {
+        let mut duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            duel.act(ALICE, Action::Attack, &mut rng),
+            Err(DuelError::NotActive)
+        );
+    }
+
+    #[test]
+    fn turns_alternate() {
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            duel.act(BOB, Action::Attack, &mut rng),
+            Err(DuelError::NotYourTurn)
+        );
+        assert_eq!(
+            duel.act(3, Action::Attack, &mut rng),
+            Err(DuelError::NotYourDuel)
+        );
+
+        duel.act(ALICE, Action::Defend, &mut rng).unwrap();
+        assert_eq!(duel.turn().id, BOB);
+        duel.act(BOB, Action::Defend, &mut rng).unwrap();
+        assert_eq!(duel.turn().id, ALICE);
+    }
+
+    #[test]
+    fn repeated_turn_is_rejected() {
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(duel.expect_turn(0), Ok(()));
+        duel.act(ALICE, Action::Defend, &mut rng).unwrap();
+        assert_eq!(duel.turns(), 1);
+        assert_eq!(duel.expect_turn(0), Err(DuelError::AlreadyPlayed));
+        assert_eq!(duel.expect_turn(1), Ok(()));
+    }
+
+    #[test]
+    fn damage_stays_in_range() {
+        for seed in 0..200 {
+            let mut duel = active();
+            let mut rng = StdRng::seed_from_u64(seed);
+            match duel.act(ALICE, Action::Attack, &mut rng).unwrap() {
+                Event::Hit { damage, crit, .. } => {
+                    let range = if crit { 16..=28 } else { ATTACK_DAMAGE };
+                    assert!(range.contains(&damage), "{damage} with seed {seed}");
+                    assert_eq!(duel.fighters[1].hp, BASE_HP - damage);
+                }
+                Event::Miss { .. } => assert_eq!(duel.fighters[1].hp, BASE_HP),
+                event => panic!("unexpected {event:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_duel() {
+        let play = |seed| {
+            let mut duel = active();
+            let mut rng = StdRng::seed_from_u64(seed);
+            while duel.phase == Phase::Active {
+                let player = duel.turn().id;
+                duel.act(player, Action::Attack, &mut rng).unwrap();
+            }
+            duel
+        };
+        assert_eq!(play(7), play(7));
+    }
+
+    #[test]
+    fn stats_raise_hp_and_damage() {
+        let strong = Stats {
+            max_hp: 150,
+            bonus_damage: 10,
+        };
+        let mut duel = Duel::challenge(ALICE, BOB, [strong, Stats::default()]).unwrap();
+        assert_eq!(duel.fighters[0].hp, 150);
+        assert_eq!(duel.fighters[1].hp, BASE_HP);
+        duel.accept(BOB).unwrap();
+
+        let hit = (0..)
+            .find_map(|seed| {
+                match duel
+                    .clone()
+                    .act(ALICE, Action::Attack, &mut StdRng::seed_from_u64(seed))
+                    .unwrap()
+                {
+                    Event::Hit {
+                        damage,
+                        crit: false,
+                        ..
+                    } => Some(damage),
+                    _ => None,
+                }
+            })
+            .unwrap();
+        assert!((18..=24).contains(&hit));
+    }
+
+    #[test]
+    fn guard_halves_the_next_hit() {
+        let mut duel = active();
+        duel.fighters[1].defending = true;
+        let hit = (0..)
+            .map(|seed| {
+                let mut duel = duel.clone();
+                let event = duel
+                    .act(ALICE, Action::Attack, &mut StdRng::seed_from_u64(seed))
+                    .unwrap();
+                (duel, event)
+            })
+            .find(|(_, event)| matches!(event, Event::Hit { .. }))
+            .unwrap();
+
+        let (
+            duel,
+            Event::Hit {
+                damage,
+                guarded,
+                crit,
+                ..
+            },
+        ) = hit
+        else {
+            unreachable!();
+        };
+        assert!(guarded);
+        let max = if crit { 14 } else { 7 };
+        assert!(damage <= max);
+        assert!(!duel.fighters[1].defending);
+    }
+
+    #[test]
+    fn heal_is_capped_and_cools_down() {
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(1);
+        duel.fighters[0].hp = BASE_HP - 5;
+        assert_eq!(
+            duel.act(ALICE, Action::Heal, &mut rng),
+            Ok(Event::Heal {
+                player: ALICE,
+                amount: 5
+            })
+        );
+        assert_eq!(duel.fighters[0].hp, BASE_HP);
+
+        duel.act(BOB, Action::Defend, &mut rng).unwrap();
+        assert_eq!(
+            duel.act(ALICE, Action::Heal, &mut rng),
+            Err(DuelError::OnCooldown(HEAL_COOLDOWN))
+        );
+        for _ in 0..HEAL_COOLDOWN {
+            duel.act(ALICE, Action::Defend, &mut rng).unwrap();
+            duel.act(BOB, Action::Defend, &mut rng).unwrap();
+        }
+        assert!(duel.act(ALICE, Action::Heal, &mut rng).is_ok());
+    }
+
+    #[test]
+    fn special_needs_charge() {
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(2);
+        assert_eq!(
+            duel.act(ALICE, Action::Special, &mut rng),
+            Err(DuelError::NotCharged(0))
+        );
+        for _ in 0..SPECIAL_CHARGE {
+            duel.act(ALICE, Action::Defend, &mut rng).unwrap();
+            duel.act(BOB, Action::Defend, &mut rng).unwrap();
+        }
+        assert_eq!(duel.fighters[0].charge, SPECIAL_CHARGE);
+        duel.act(ALICE, Action::Special, &mut rng).unwrap();
+        assert_eq!(duel.fighters[0].charge, 0);
+    }
+
+    #[test]
+    fn knockout_finishes_the_duel() {
+        let mut duel = active();
+        duel.fighters[1].hp = 1;
+        let mut rng = StdRng::seed_from_u64(0);
+        while duel.phase == Phase::Active {
+            let player = duel.turn().id;
+            let action = if player == ALICE {
+                Action::Attack
+            } else {
+                Action::Defend
+            };
+            duel.act(player, action, &mut rng).unwrap();
+        }
+        assert_eq!(duel.phase, Phase::Finished { winner: ALICE });
+        assert!(duel.phase.is_over());
+        assert_eq!(duel.fighters[1].hp, 0);
+        assert_eq!(
+            duel.act(BOB, Action::Attack, &mut rng),
+            Err(DuelError::NotActive)
+        );
+    }
+
+    #[test]
+    fn time_out_expires_or_forfeits() {
+        let mut duel = Duel::challenge(ALICE, BOB, BASE).unwrap();
+        assert_eq!(duel.time_out(), None);
+        assert_eq!(duel.phase, Phase::Expired);
+
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(0);
+        duel.act(ALICE, Action::Defend, &mut rng).unwrap();
+        assert_eq!(duel.time_out(), Some(Event::Forfeit { player: BOB }));
+        assert_eq!(duel.phase, Phase::Finished { winner: ALICE });
+    }
+
+    #[test]
+    fn log_keeps_the_last_turns() {
+        let mut duel = active();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..LOG_LINES + 3 {
+            let player = duel.turn().id;
+            duel.act(player, Action::Defend, &mut rng).unwrap();
+        }
+        assert_eq!(duel.log.len(), LOG_LINES);
+    }
+}
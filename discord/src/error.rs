@@ -0,0 +1,194 @@
+use std::fmt;
+
+use poise::{
+    CreateReply, FrameworkError,
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter},
+};
+use redis::RedisError;
+
+use crate::{Context, Data, rpc::RpcError};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The user asked for something that can't be done, the message is shown to them.
+    User(String),
+    /// The user isn't allowed to do this, the message is shown to them.
+    Permission(String),
+    Discord(Box<serenity::Error>),
+    Redis(RedisError),
+    Rpc(RpcError),
+    Config(String),
+    /// A bridge event that doesn't have the expected shape.
+    Event(String),
+}
+
+impl Error {
+    pub fn user(message: impl Into<String>) -> Self {
+        Error::User(message.into())
+    }
+
+    pub fn permission(message: impl Into<String>) -> Self {
+        Error::Permission(message.into())
+    }
+
+    pub fn event(message: impl Into<String>) -> Self {
+        Error::Event(message.into())
+    }
+
+    /// Short name of the variant, used as a metric label and log field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::User(_) => "user",
+            Error::Permission(_) => "permission",
+            Error::Discord(_) => "discord",
+            Error::Redis(_) => "redis",
+            Error::Rpc(_) => "rpc",
+            Error::Config(_) => "config",
+            Error::Event(_) => "event",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::User(e) | Error::Permission(e) => write!(f, "{e}"),
+            Error::Discord(e) => write!(f, "Discord error: {e}"),
+            Error::Redis(e) => write!(f, "Redis error: {e}"),
+            Error::Rpc(e) => write!(f, "Twitch error: {e}"),
+            Error::Config(e) => write!(f, "Configuration error: {e}"),
+            Error::Event(e) => write!(f, "Invalid event: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Discord(e) => Some(e.as_ref()),
+            Error::Redis(e) => Some(e),
+            Error::Rpc(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(e: serenity::Error) -> Self {
+        Error::Discord(Box::new(e))
+    }
+}
+
+impl From<RedisError> for Error {
+    fn from(e: RedisError) -> Self {
+        Error::Redis(e)
+    }
+}
+
+impl From<RpcError> for Error {
+    fn from(e: RpcError) -> Self {
+        Error::Rpc(e)
+    }
+}
+
+async fn send_error(ctx: Context<'_>, title: &str, description: String) -> Result<(), Error> {
+    let bot_img = ctx
+        .framework()
+        .bot_id
+        .to_user(&ctx.http())
+        .await?
+        .avatar_url()
+        .unwrap_or("".into());
+
+    let author_img = ctx.author().avatar_url().unwrap_or("".into());
+
+    ctx.send(CreateReply {
+        embeds: vec![
+            CreateEmbed::new()
+                .title(title)
+                .description(description)
+                .author(
+                    CreateEmbedAuthor::new(ctx.author().display_name())
+                        .icon_url(author_img.to_owned()),
+                )
+                .thumbnail(bot_img.to_owned())
+                .color(serenity::Colour::from_rgb(220, 40, 40))
+                .footer(
+                    CreateEmbedFooter::new(ctx.invocation_string()).icon_url(bot_img.to_owned()),
+                )
+                .timestamp(chrono::Utc::now()),
+        ],
+        ephemeral: Some(true),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// Shows user and permission errors to the invoker and logs everything else.
+pub async fn on_error(error: FrameworkError<'_, Data, Error>) {
+    let result = match error {
+        FrameworkError::Command { error, ctx, .. } => match error {
+            Error::User(message) => send_error(ctx, "❌ Can't do that", message).await,
+            Error::Permission(message) => send_error(ctx, "⛔ Not allowed", message).await,
+            error => {
+                tracing::error!(
+                    command = %ctx.command().qualified_name,
+                    user = %ctx.author().id,
+                    kind = error.kind(),
+                    error = %error,
+                    "Command failed"
+                );
+                send_error(
+                    ctx,
+                    "💥 Something went wrong",
+                    String::from("The command failed, please try again later."),
+                )
+                .await
+            }
+        },
+        FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => match error {
+            Error::Permission(message) | Error::User(message) => {
+                send_error(ctx, "⛔ Not allowed", message).await
+            }
+            error => {
+                tracing::error!(
+                    command = %ctx.command().qualified_name,
+                    kind = error.kind(),
+                    error = %error,
+                    "Command check failed"
+                );
+                Ok(())
+            }
+        },
+        FrameworkError::MissingUserPermissions {
+            missing_permissions,
+            ctx,
+            ..
+        } => {
+            let description = match missing_permissions {
+                Some(permissions) => format!("You need the `{permissions}` permission(s)."),
+                None => String::from("You don't have the permissions to use this command."),
+            };
+            send_error(ctx, "⛔ Not allowed", description).await
+        }
+        FrameworkError::EventHandler { error, event, .. } => {
+            tracing::error!(
+                event = event.snake_case_name(),
+                kind = error.kind(),
+                error = %error,
+                "Event handler failed"
+            );
+            Ok(())
+        }
+        error => poise::builtins::on_error(error).await.map_err(Error::from),
+    };
+
+    if let Err(e) = result {
+        tracing::error!(error = %e, "Failed to report an error");
+    }
+}
@@ -1,21 +1,57 @@
 use poise::serenity_prelude::{
-    self as serenity, ButtonStyle, CreateActionRow, CreateButton, EmojiId, Interaction,
-    ReactionType, User,
+    self as serenity, ActionRowComponent, ButtonStyle, ComponentInteraction, CreateActionRow,
+    CreateButton, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, EmojiId, Interaction, ReactionType, User,
 };
 
-use crate::{Data, Error};
+use crate::{
+    Data, Error,
+    config::Config,
+    duels::{
+        self, DuelsConfig,
+        engine::{Action, Duel, DuelError, SPECIAL_CHARGE},
+    },
+};
 
 mod accept_duel;
-mod attack_action;
 mod cancel_duel;
+mod combat_action;
+mod place_bet;
+
+/// Action buttons of the player whose turn it is, and who the duel is waiting for.
+///
+/// The ids carry the turn number so repeated clicks only play once. Spectators get a
+/// button to bet while `bets_open`.
+fn create_combat_ui(turn_user: &User, duel: &Duel, bets_open: bool) -> Vec<CreateActionRow> {
+    let fighter = duel.turn();
+    let turn = duel.turns();
 
-fn create_combat_ui(turn_user: &User) -> Vec<CreateActionRow> {
-    let attack_button = CreateButton::new("attack_action")
+    let attack_button = CreateButton::new(format!("attack_action:{turn}"))
         .style(ButtonStyle::Primary)
         .label("Attack!")
         .emoji(ReactionType::Unicode("⚔️".to_string()))
         .disabled(false);
 
+    let defend_button = CreateButton::new(format!("defend_action:{turn}"))
+        .style(ButtonStyle::Secondary)
+        .label("Defend")
+        .emoji(ReactionType::Unicode("🛡️".to_string()));
+
+    let heal_button = CreateButton::new(format!("heal_action:{turn}"))
+        .style(ButtonStyle::Success)
+        .label(match fighter.heal_cooldown {
+            0 => String::from("Heal"),
+            turns => format!("Heal ({turns})"),
+        })
+        .emoji(ReactionType::Unicode("💚".to_string()))
+        .disabled(fighter.check(Action::Heal).is_err());
+
+    let special_button = CreateButton::new(format!("special_action:{turn}"))
+        .style(ButtonStyle::Danger)
+        .label(format!("Special ({}/{SPECIAL_CHARGE})", fighter.charge))
+        .emoji(ReactionType::Unicode("🔥".to_string()))
+        .disabled(fighter.check(Action::Special).is_err());
+
     let disabled_button = CreateButton::new("wait_turn")
         .style(ButtonStyle::Secondary)
         .label(format!("Waiting for {}...", turn_user.name))
@@ -26,30 +62,178 @@ fn create_combat_ui(turn_user: &User) -> Vec<CreateActionRow> {
         })
         .disabled(true);
 
-    let buttons = vec![attack_button, disabled_button];
+    let buttons = vec![
+        attack_button,
+        defend_button,
+        heal_button,
+        special_button,
+        disabled_button,
+    ];
 
-    vec![CreateActionRow::Buttons(buttons)]
+    let mut rows = vec![CreateActionRow::Buttons(buttons)];
+    if bets_open {
+        rows.push(CreateActionRow::Buttons(vec![
+            CreateButton::new("place_bet")
+                .style(ButtonStyle::Secondary)
+                .label("Place bet")
+                .emoji(ReactionType::Unicode("🎲".to_string())),
+        ]));
+    }
+    rows
 }
 
+/// Tells the clicker why their click didn't change the duel.
+async fn duel_rejected(
+    ctx: &serenity::Context,
+    component: &ComponentInteraction,
+    error: DuelError,
+) -> Result<(), Error> {
+    let response = match error {
+        // A repeated click, the first one already updates the message.
+        DuelError::AlreadyPlayed => CreateInteractionResponse::Acknowledge,
+        error => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(error.to_string())
+                .ephemeral(true),
+        ),
+    };
+    component.create_response(&ctx, response).await?;
+    Ok(())
+}
+
+/// Disables the buttons of a duel the bot no longer knows about and tells the clicker why.
+async fn duel_expired(
+    ctx: &serenity::Context,
+    component: &ComponentInteraction,
+) -> Result<(), Error> {
+    let components = component
+        .message
+        .components
+        .iter()
+        .map(|row| {
+            CreateActionRow::Buttons(
+                row.components
+                    .iter()
+                    .filter_map(|component| match component {
+                        ActionRowComponent::Button(button) => {
+                            Some(CreateButton::from(button.clone()).disabled(true))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+
+    component
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().components(components),
+            ),
+        )
+        .await?;
+    component
+        .create_followup(
+            &ctx,
+            CreateInteractionResponseFollowup::new()
+                .content("⌛ This duel has expired.")
+                .ephemeral(true),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Duel settings of a guild, betting is off when the guild's economy is.
+fn duels_config(config: &Config) -> DuelsConfig {
+    let mut duels = config.duels.clone();
+    if !config.feature_enabled("economy") {
+        duels.betting_turns = 0;
+    }
+    duels
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        interaction_id = %interaction.id(),
+        kind = ?interaction.kind(),
+        user = tracing::field::Empty,
+        custom_id = tracing::field::Empty,
+    )
+)]
 pub async fn interaction_create(
     ctx: &serenity::Context,
     interaction: Interaction,
     framework: poise::FrameworkContext<'_, Data, Error>,
     _data: &Data,
 ) -> Result<(), Error> {
-    if let serenity::Interaction::Component(component) = interaction {
-        let data = framework.user_data().await.active_duels.lock().await;
+    if let serenity::Interaction::Modal(modal) = interaction {
+        let span = tracing::Span::current();
+        span.record("user", modal.user.id.get());
+        span.record("custom_id", modal.data.custom_id.as_str());
+        tracing::debug!("Modal submitted");
+
+        if modal.data.custom_id != "place_bet" {
+            return Ok(());
+        }
+        let data = framework.user_data().await;
+        let config = data.settings.config(modal.guild_id).await;
+        place_bet::submit_bet(
+            ctx,
+            &data.duels,
+            &data.bets,
+            &data.wallets,
+            &duels_config(&config),
+            config.economy.starting_grant,
+            modal,
+        )
+        .await?;
+    } else if let serenity::Interaction::Component(component) = interaction {
+        let span = tracing::Span::current();
+        span.record("user", component.user.id.get());
+        span.record("custom_id", component.data.custom_id.as_str());
+        tracing::debug!("Component interaction received");
+
+        let Some((button, turn)) = duels::parse_button(&component.data.custom_id) else {
+            return Ok(());
+        };
 
-        let game_state_option = data.get(&component.message.id).cloned();
+        let data = framework.user_data().await;
+        let config = data.settings.config(component.guild_id).await;
+        let duels_config = duels_config(&config);
 
-        if let Some(game_state) = game_state_option {
-            match component.data.custom_id.as_str() {
-                "accept_duel" => accept_duel::accept_duel(ctx, component, game_state).await?,
-                "cancel_duel" => cancel_duel::cancel_duel(ctx, data, component, game_state).await?,
-                "attack_action" => {
-                    attack_action::attack_action(ctx, data, component, game_state).await?
+        match button {
+            "accept_duel" => {
+                accept_duel::accept_duel(
+                    ctx,
+                    &data.duels,
+                    &data.wallets,
+                    &data.duel_results,
+                    &duels_config,
+                    component,
+                )
+                .await?
+            }
+            "cancel_duel" => {
+                cancel_duel::cancel_duel(ctx, &data.duels, &data.duel_results, component).await?
+            }
+            "place_bet" => {
+                place_bet::open_bet_modal(ctx, &data.duels, &duels_config, component).await?
+            }
+            button => {
+                if let Some(action) = combat_action::action(button) {
+                    combat_action::combat_action(
+                        ctx,
+                        &data.duels,
+                        &data.duel_results,
+                        &duels_config,
+                        component,
+                        action,
+                        turn,
+                    )
+                    .await?
                 }
-                _ => {}
             }
         }
     }
@@ -1,50 +1,154 @@
 use poise::serenity_prelude::{
     self as serenity, ComponentInteraction, CreateEmbed, CreateInteractionResponse,
-    CreateInteractionResponseMessage,
+    CreateInteractionResponseMessage, MessageId, UserId,
 };
+use redis::RedisResult;
 
-use super::create_combat_ui;
-use crate::{Error, GameState};
+use super::{
+    combat_action::fighter_fields, create_combat_ui, duel_expired, duel_rejected,
+    place_bet::bets_field,
+};
+use crate::{
+    Error, GameState,
+    bets::Pools,
+    duels::{
+        DuelResults, Duels, DuelsConfig, Transition,
+        engine::{DuelError, Phase},
+    },
+    wallets::{CURRENCY, Wallets},
+};
+
+/// Why a click didn't start the duel.
+#[derive(Debug, PartialEq, Eq)]
+enum Refusal {
+    Duel(DuelError),
+    /// This player doesn't have the coins for the wager.
+    CantCover(UserId),
+}
+
+/// What a duel puts at stake when it starts, the duel results outside of tests.
+trait Stakes {
+    async fn hold_wager(
+        &self,
+        message_id: MessageId,
+        state: &GameState,
+    ) -> RedisResult<Result<(), UserId>>;
+
+    async fn open_bets(&self, message_id: MessageId) -> RedisResult<()>;
+}
+
+impl Stakes for DuelResults {
+    async fn hold_wager(
+        &self,
+        message_id: MessageId,
+        state: &GameState,
+    ) -> RedisResult<Result<(), UserId>> {
+        DuelResults::hold_wager(self, message_id, state).await
+    }
+
+    async fn open_bets(&self, message_id: MessageId) -> RedisResult<()> {
+        DuelResults::open_bets(self, message_id).await
+    }
+}
+
+/// Starts the duel once the wager of both players is taken, and opens the bets.
+///
+/// Runs while the duel is locked, so only the click that starts it takes the coins.
+async fn start(
+    state: &mut GameState,
+    message_id: MessageId,
+    user_id: UserId,
+    stakes: &impl Stakes,
+    config: &DuelsConfig,
+) -> RedisResult<Result<(), Refusal>> {
+    match state.duel.accept(user_id.get()) {
+        Ok(()) => {}
+        // The opponent clicked again, their first click already started the duel.
+        Err(DuelError::NotPending)
+            if user_id.get() == state.duel.opponent() && state.duel.phase == Phase::Active =>
+        {
+            return Ok(Err(Refusal::Duel(DuelError::AlreadyPlayed)));
+        }
+        Err(e) => return Ok(Err(Refusal::Duel(e))),
+    }
+    if let Err(player) = stakes.hold_wager(message_id, state).await? {
+        return Ok(Err(Refusal::CantCover(player)));
+    }
+    if config.bets_open(&state.duel) {
+        stakes.open_bets(message_id).await?;
+    }
+    state.deadline = config.turn_deadline();
+    Ok(Ok(()))
+}
 
 pub async fn accept_duel(
     ctx: &serenity::Context,
+    duels: &Duels,
+    wallets: &Wallets,
+    results: &DuelResults,
+    config: &DuelsConfig,
     component: ComponentInteraction,
-    game_state: GameState,
 ) -> Result<(), Error> {
-    if component.user.id != game_state.player2_id {
-        component
-            .create_response(
-                &ctx,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .content("This duel challenge is not for you!")
-                        .ephemeral(true),
-                ),
-            )
-            .await
-            .ok();
-        return Ok(());
-    }
-
-    let p1_user = game_state.player1_id.to_user(&ctx).await.unwrap();
-    let p2_user = game_state.player2_id.to_user(&ctx).await.unwrap();
-    let new_embed = CreateEmbed::new()
+    let message_id = component.message.id;
+    let mut wager = 0;
+    let transition = duels
+        .update_with(message_id, async |state| {
+            wager = state.wager;
+            start(state, message_id, component.user.id, results, config).await
+        })
+        .await?;
+    let game_state = match transition {
+        Transition::Missing => return duel_expired(ctx, &component).await,
+        Transition::Rejected(Refusal::Duel(e)) => {
+            return duel_rejected(ctx, &component, e).await;
+        }
+        Transition::Rejected(Refusal::CantCover(user_id)) => {
+            let balance = match component.guild_id {
+                Some(guild_id) => wallets.balance(guild_id, user_id).await?,
+                None => 0,
+            };
+            let who = if user_id == component.user.id {
+                String::from("You only have")
+            } else {
+                format!("<@{user_id}> only has")
+            };
+            component
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!(
+                                "{who} **{balance}** {CURRENCY}, the wager is **{wager}** {CURRENCY}."
+                            ))
+                            .ephemeral(true),
+                    ),
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+        Transition::Applied(game_state, ()) => game_state,
+    };
+    let deadline = game_state.deadline;
+
+    let p1_user = UserId::new(game_state.duel.challenger())
+        .to_user(&ctx)
+        .await?;
+    let p2_user = UserId::new(game_state.duel.opponent())
+        .to_user(&ctx)
+        .await?;
+    let mut new_embed = CreateEmbed::new()
         .title("Duel Started!")
         .description(format!(
-            "{p1_user} vs {p2_user}\n\nIt is {p1_user}'s turn to attack!",
+            "{p1_user} vs {p2_user}\n\nIt is {p1_user}'s turn to act! The turn ends <t:{deadline}:R>.",
         ))
-        .field(
-            format!("{}`s HP", p1_user.name),
-            game_state.player1_hp.to_string(),
-            true,
-        )
-        .field(
-            format!("{}`s HP", p2_user.name),
-            game_state.player2_hp.to_string(),
-            true,
-        );
+        .fields(fighter_fields(&game_state.duel, &p1_user.name, &p2_user.name));
+    let bets_open = config.bets_open(&game_state.duel);
+    if bets_open {
+        new_embed = new_embed.fields([bets_field(&game_state.duel, &Pools::default(), bets_open)]);
+    }
 
-    let components = create_combat_ui(&p1_user);
+    let components = create_combat_ui(&p1_user, &game_state.duel, bets_open);
 
     component
         .create_response(
@@ -59,3 +163,135 @@ pub async fn accept_duel(
         .ok();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use poise::serenity_prelude::{ChannelId, GuildId};
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::duels::engine::{Duel, Stats};
+
+    const CHALLENGER: u64 = 1;
+    const OPPONENT: u64 = 2;
+    const MESSAGE: MessageId = MessageId::new(10);
+
+    /// Counts the wagers taken and the bets opened, everyone can cover the wagers.
+    #[derive(Default)]
+    struct Escrow {
+        holds: AtomicU32,
+        opened: AtomicU32,
+    }
+
+    impl Stakes for Escrow {
+        async fn hold_wager(
+            &self,
+            _message_id: MessageId,
+            _state: &GameState,
+        ) -> RedisResult<Result<(), UserId>> {
+            self.holds.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            Ok(Ok(()))
+        }
+
+        async fn open_bets(&self, _message_id: MessageId) -> RedisResult<()> {
+            self.opened.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn challenge() -> GameState {
+        GameState {
+            duel: Duel::challenge(CHALLENGER, OPPONENT, [Stats::default(); 2]).unwrap(),
+            guild_id: Some(GuildId::new(1)),
+            channel_id: ChannelId::new(1),
+            deadline: 0,
+            wager: 50,
+        }
+    }
+
+    /// A click on Accept, under the duel's lock like [`Duels::update_with`].
+    async fn click(duel: &Mutex<GameState>, escrow: &Escrow) -> Result<(), Refusal> {
+        let mut current = duel.lock().await;
+        let mut state = current.clone();
+        let outcome = start(
+            &mut state,
+            MESSAGE,
+            UserId::new(OPPONENT),
+            escrow,
+            &DuelsConfig::default(),
+        )
+        .await
+        .unwrap();
+        if outcome.is_ok() {
+            *current = state;
+        }
+        outcome
+    }
+
+    #[tokio::test]
+    async fn double_click_takes_the_wager_once() {
+        let duel = Arc::new(Mutex::new(challenge()));
+        let escrow = Arc::new(Escrow::default());
+
+        let clicks = [0, 1].map(|_| {
+            let (duel, escrow) = (duel.clone(), escrow.clone());
+            tokio::spawn(async move { click(&duel, &escrow).await })
+        });
+        let mut outcomes = vec![];
+        for click in clicks {
+            outcomes.push(click.await.unwrap());
+        }
+
+        outcomes.sort_by_key(Result::is_err);
+        assert_eq!(
+            outcomes,
+            vec![Ok(()), Err(Refusal::Duel(DuelError::AlreadyPlayed))]
+        );
+        assert_eq!(escrow.holds.load(Ordering::SeqCst), 1);
+        assert_eq!(escrow.opened.load(Ordering::SeqCst), 1);
+        assert_eq!(duel.lock().await.duel.phase, Phase::Active);
+    }
+
+    #[tokio::test]
+    async fn challenger_cant_start_the_duel() {
+        let escrow = Escrow::default();
+
+        let mut state = challenge();
+        let outcome = start(
+            &mut state,
+            MESSAGE,
+            UserId::new(CHALLENGER),
+            &escrow,
+            &DuelsConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(outcome, Err(Refusal::Duel(_))));
+        assert_eq!(escrow.holds.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn only_the_opponent_clicks_again_silently() {
+        let escrow = Escrow::default();
+        let duel = Mutex::new(challenge());
+        click(&duel, &escrow).await.unwrap();
+
+        let mut state = duel.lock().await.clone();
+        let outcome = start(
+            &mut state,
+            MESSAGE,
+            UserId::new(CHALLENGER),
+            &escrow,
+            &DuelsConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome, Err(Refusal::Duel(DuelError::NotPending)));
+    }
+}
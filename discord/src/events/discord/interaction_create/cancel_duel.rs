@@ -1,35 +1,32 @@
-use std::collections::HashMap;
-
 use poise::serenity_prelude::{
     self as serenity, ComponentInteraction, CreateEmbed, CreateInteractionResponse,
-    CreateInteractionResponseMessage, MessageId,
+    CreateInteractionResponseMessage,
 };
-use tokio::sync::MutexGuard;
 
-use crate::{Error, GameState};
+use super::{duel_expired, duel_rejected};
+use crate::{
+    Error,
+    duels::{DuelResults, Duels, Transition},
+};
 
 pub async fn cancel_duel(
     ctx: &serenity::Context,
-    mut data: MutexGuard<'_, HashMap<MessageId, GameState>>,
+    duels: &Duels,
+    results: &DuelResults,
     component: ComponentInteraction,
-    game_state: GameState,
 ) -> Result<(), Error> {
-    if component.user.id != game_state.player1_id && component.user.id != game_state.player2_id {
-        component
-            .create_response(
-                &ctx,
-                CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .content("This duel challenge is not for you!")
-                        .ephemeral(true),
-                ),
-            )
-            .await
-            .ok();
-        return Ok(());
-    }
+    let transition = duels
+        .update(component.message.id, |state| {
+            state.duel.cancel(component.user.id.get())
+        })
+        .await?;
+    let game_state = match transition {
+        Transition::Missing => return duel_expired(ctx, &component).await,
+        Transition::Rejected(e) => return duel_rejected(ctx, &component, e).await,
+        Transition::Applied(game_state, ()) => game_state,
+    };
+    results.settle(component.message.id, &game_state).await;
 
-    data.remove(&component.message.id);
     let new_embed = CreateEmbed::new()
         .title("Duel Cancelled")
         .description("The duel has been cancelled.");
@@ -0,0 +1,200 @@
+use poise::serenity_prelude::{
+    self as serenity, Colour, ComponentInteraction, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, UserId,
+};
+
+use super::{create_combat_ui, duel_expired, duel_rejected, place_bet::bets_field};
+use crate::{
+    Error,
+    duels::{
+        DuelResults, Duels, DuelsConfig, Transition,
+        engine::{Action, Duel, Event, Phase, SPECIAL_CHARGE},
+    },
+};
+
+pub fn action(custom_id: &str) -> Option<Action> {
+    match custom_id {
+        "attack_action" => Some(Action::Attack),
+        "defend_action" => Some(Action::Defend),
+        "heal_action" => Some(Action::Heal),
+        "special_action" => Some(Action::Special),
+        _ => None,
+    }
+}
+
+/// Combat log line of a turn.
+pub fn describe(event: &Event) -> String {
+    match *event {
+        Event::Hit {
+            attacker,
+            defender,
+            damage,
+            crit,
+            guarded,
+            special,
+        } => {
+            let mut line = if special {
+                format!(
+                    "🔥 <@{attacker}> unleashes a special attack on <@{defender}> for **{damage}**"
+                )
+            } else {
+                format!("⚔️ <@{attacker}> hits <@{defender}> for **{damage}**")
+            };
+            if crit {
+                line.push_str(", critical hit!");
+            }
+            if guarded {
+                line.push_str(" (guarded)");
+            }
+            line
+        }
+        Event::Miss {
+            attacker, defender, ..
+        } => format!("💨 <@{attacker}> misses <@{defender}>"),
+        Event::Guard { player } => format!("🛡️ <@{player}> raises their guard"),
+        Event::Heal { player, amount } => format!("💚 <@{player}> heals **{amount}** HP"),
+        Event::Forfeit { player } => format!("⌛ <@{player}> ran out of time"),
+    }
+}
+
+/// Plays a turn, `turn` is the turn the clicked buttons were shown on.
+pub async fn combat_action(
+    ctx: &serenity::Context,
+    duels: &Duels,
+    results: &DuelResults,
+    config: &DuelsConfig,
+    component: ComponentInteraction,
+    action: Action,
+    turn: Option<u32>,
+) -> Result<(), Error> {
+    let message_id = component.message.id;
+    let transition = duels
+        .update_with(message_id, async |state| {
+            if let Some(turn) = turn
+                && let Err(e) = state.duel.expect_turn(turn)
+            {
+                return Ok(Err(e));
+            }
+            let event = match state
+                .duel
+                .act(component.user.id.get(), action, &mut rand::rng())
+            {
+                Ok(event) => event,
+                Err(e) => return Ok(Err(e)),
+            };
+            // Bets placed after this are refused, the ones already in stay.
+            if !config.bets_open(&state.duel) {
+                results.close_bets(message_id).await?;
+            }
+            state.deadline = config.turn_deadline();
+            Ok(Ok(event))
+        })
+        .await?;
+    let (game_state, event) = match transition {
+        Transition::Missing => return duel_expired(ctx, &component).await,
+        Transition::Rejected(e) => return duel_rejected(ctx, &component, e).await,
+        Transition::Applied(game_state, event) => (game_state, event),
+    };
+
+    let p1_user = UserId::new(game_state.duel.challenger())
+        .to_user(&ctx)
+        .await?;
+    let p2_user = UserId::new(game_state.duel.opponent())
+        .to_user(&ctx)
+        .await?;
+
+    let line = describe(&event);
+    let finished = matches!(game_state.duel.phase, Phase::Finished { .. });
+    let description = match game_state.duel.phase {
+        Phase::Finished { winner } => {
+            let (winner_user, loser_user) = if winner == p1_user.id.get() {
+                (&p1_user, &p2_user)
+            } else {
+                (&p2_user, &p1_user)
+            };
+            let results = results.settle(message_id, &game_state).await;
+            format!(
+                "{line}\n\n{winner_user} defeated {loser_user}!\n{}",
+                results.join("\n")
+            )
+        }
+        _ => format!(
+            "{line}\n\nIt is now <@{}>'s turn, it ends <t:{}:R>.",
+            game_state.duel.turn().id,
+            game_state.deadline
+        ),
+    };
+
+    let mut new_embed = CreateEmbed::new()
+        .title(if finished {
+            "Duel Finished"
+        } else {
+            "Duel in Progress"
+        })
+        .description(description)
+        .fields(fighter_fields(
+            &game_state.duel,
+            &p1_user.name,
+            &p2_user.name,
+        ))
+        .field("Combat Log", combat_log(&game_state.duel), false)
+        .color(Colour::from_rgb(0, 100, 255));
+    // Bets were paid out with the results once the duel finished.
+    let bets_open = config.bets_open(&game_state.duel);
+    if !finished {
+        let pools = results
+            .bet_pools(message_id)
+            .await
+            .inspect_err(|e| tracing::warn!(error = %e, "Failed to read duel bets"))
+            .unwrap_or_default();
+        if bets_open || pools.total() > 0 {
+            new_embed = new_embed.fields([bets_field(&game_state.duel, &pools, bets_open)]);
+        }
+    }
+
+    let components = if finished {
+        vec![]
+    } else {
+        let next_user = if game_state.duel.turn().id == p1_user.id.get() {
+            &p1_user
+        } else {
+            &p2_user
+        };
+        create_combat_ui(next_user, &game_state.duel, bets_open)
+    };
+
+    component
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embeds(vec![new_embed])
+                    .components(components),
+            ),
+        )
+        .await
+        .ok();
+    Ok(())
+}
+
+fn combat_log(duel: &Duel) -> String {
+    duel.log.iter().map(describe).collect::<Vec<_>>().join("\n")
+}
+
+/// HP and special charge of both players, as inline embed fields.
+pub fn fighter_fields(duel: &Duel, p1_name: &str, p2_name: &str) -> Vec<(String, String, bool)> {
+    [p1_name, p2_name]
+        .into_iter()
+        .zip(&duel.fighters)
+        .map(|(name, fighter)| {
+            let mut value = format!(
+                "❤️ {}/{}\n🔥 {}/{SPECIAL_CHARGE}",
+                fighter.hp, fighter.max_hp, fighter.charge
+            );
+            if fighter.defending {
+                value.push_str("\n🛡️ Guarding");
+            }
+            (format!("{name}`s HP"), value, true)
+        })
+        .collect()
+}
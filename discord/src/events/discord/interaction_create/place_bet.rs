@@ -0,0 +1,247 @@
+use poise::serenity_prelude::{
+    self as serenity, ActionRowComponent, ComponentInteraction, CreateActionRow, CreateEmbed,
+    CreateInputText, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, CreateModal, InputTextStyle, ModalInteraction, UserId,
+};
+
+use super::duel_expired;
+use crate::{
+    Error,
+    bets::{BetError, Bets, Pools},
+    duels::{Duels, DuelsConfig, engine::Duel},
+    wallets::{CURRENCY, Wallets},
+};
+
+const BETS_FIELD: &str = "Bets";
+
+/// Coins on each player with their parimutuel odds, as an embed field.
+pub fn bets_field(duel: &Duel, pools: &Pools, open: bool) -> (String, String, bool) {
+    let mut value = [duel.challenger(), duel.opponent()]
+        .into_iter()
+        .enumerate()
+        .map(|(side, player)| {
+            let odds = match pools.amounts[side] {
+                0 => String::new(),
+                amount => format!(", pays x{:.2}", pools.total() as f64 / amount as f64),
+            };
+            format!(
+                "<@{player}>: **{}** {CURRENCY} ({} bets{odds})",
+                pools.amounts[side], pools.bettors[side]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    value.push_str(if open {
+        "\n🎲 Bets are open"
+    } else {
+        "\n🔒 Bets are closed"
+    });
+    (String::from(BETS_FIELD), value, false)
+}
+
+async fn reply(
+    ctx: &serenity::Context,
+    modal: &ModalInteraction,
+    content: String,
+) -> Result<(), Error> {
+    modal
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Why `user_id` can't bet on `duel`, if they can't.
+fn refuse_bet(duel: &Duel, config: &DuelsConfig, user_id: UserId) -> Option<&'static str> {
+    if duel.is_player(user_id.get()) {
+        Some("You can't bet on your own duel!")
+    } else if !config.bets_open(duel) {
+        Some("🔒 Bets on this duel are closed.")
+    } else {
+        None
+    }
+}
+
+/// Asks a spectator how much they bet, and on whom.
+pub async fn open_bet_modal(
+    ctx: &serenity::Context,
+    duels: &Duels,
+    config: &DuelsConfig,
+    component: ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(state) = duels.peek(component.message.id).await else {
+        return duel_expired(ctx, &component).await;
+    };
+    if let Some(reason) = refuse_bet(&state.duel, config, component.user.id) {
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(reason)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let p1_user = UserId::new(state.duel.challenger()).to_user(&ctx).await?;
+    let p2_user = UserId::new(state.duel.opponent()).to_user(&ctx).await?;
+    let modal = CreateModal::new("place_bet", "Place a bet").components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Amount", "amount")
+                .placeholder(format!("Coins to stake, in {CURRENCY}")),
+        ),
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, "Side", "side")
+                .placeholder(format!("1 for {}, 2 for {}", p1_user.name, p2_user.name))
+                .max_length(1),
+        ),
+    ]);
+
+    component
+        .create_response(&ctx, CreateInteractionResponse::Modal(modal))
+        .await?;
+    Ok(())
+}
+
+/// Places the bet entered in the modal and updates the totals on the duel message.
+pub async fn submit_bet(
+    ctx: &serenity::Context,
+    duels: &Duels,
+    bets: &Bets,
+    wallets: &Wallets,
+    config: &DuelsConfig,
+    starting_grant: u64,
+    modal: ModalInteraction,
+) -> Result<(), Error> {
+    let Some(message) = modal.message.as_deref() else {
+        return Ok(());
+    };
+    let Some(state) = duels.peek(message.id).await else {
+        return reply(ctx, &modal, String::from("⌛ This duel has ended.")).await;
+    };
+    let Some(guild_id) = state.guild_id else {
+        return Ok(());
+    };
+    if let Some(reason) = refuse_bet(&state.duel, config, modal.user.id) {
+        return reply(ctx, &modal, String::from(reason)).await;
+    }
+
+    let input = |custom_id: &str| {
+        modal
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == custom_id => {
+                    input.value.as_deref().map(str::trim)
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+    let Some(amount) = input("amount")
+        .parse::<u64>()
+        .ok()
+        .filter(|&amount| amount > 0)
+    else {
+        return reply(ctx, &modal, String::from("Bet a whole number of coins.")).await;
+    };
+    let side = match input("side") {
+        "1" => 0,
+        "2" => 1,
+        _ => return reply(ctx, &modal, String::from("Pick side 1 or 2.")).await,
+    };
+    let player = state.duel.fighters[side].id;
+
+    let balance = wallets
+        .open(guild_id, modal.user.id, starting_grant)
+        .await?;
+    match bets
+        .place(guild_id, message.id, side, modal.user.id, amount)
+        .await?
+    {
+        Ok(()) => {}
+        Err(BetError::OtherSide) => {
+            return reply(
+                ctx,
+                &modal,
+                String::from("You already bet on the other player!"),
+            )
+            .await;
+        }
+        Err(BetError::Closed) => {
+            return reply(
+                ctx,
+                &modal,
+                String::from("🔒 Bets on this duel are closed."),
+            )
+            .await;
+        }
+        Err(BetError::Insufficient) => {
+            return reply(
+                ctx,
+                &modal,
+                format!("You only have **{balance}** {CURRENCY}."),
+            )
+            .await;
+        }
+    }
+    tracing::info!(message_id = %message.id, user = %modal.user.id, side, amount, "Bet placed");
+
+    // The rest of the message stays as the last turn left it.
+    let pools = bets.pools(message.id).await?;
+    let mut embed = CreateEmbed::new();
+    if let Some(current) = message.embeds.first() {
+        if let Some(title) = &current.title {
+            embed = embed.title(title);
+        }
+        if let Some(description) = &current.description {
+            embed = embed.description(description);
+        }
+        if let Some(colour) = current.colour {
+            embed = embed.colour(colour);
+        }
+        embed = embed.fields(
+            current
+                .fields
+                .iter()
+                .filter(|field| field.name != BETS_FIELD)
+                .map(|field| (field.name.clone(), field.value.clone(), field.inline)),
+        );
+    }
+    embed = embed.fields([bets_field(
+        &state.duel,
+        &pools,
+        config.bets_open(&state.duel),
+    )]);
+
+    modal
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().embed(embed),
+            ),
+        )
+        .await?;
+    modal
+        .create_followup(
+            &ctx,
+            CreateInteractionResponseFollowup::new()
+                .content(format!(
+                    "🎲 You bet **{amount}** {CURRENCY} on <@{player}>."
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+    Ok(())
+}
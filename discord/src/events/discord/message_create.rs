@@ -1,47 +1,54 @@
-use crate::load_config;
+use crate::{Data, logging};
 use poise::serenity_prelude::Message;
-use redis::{AsyncCommands, Client, RedisResult};
-
-pub async fn message_create(msg: Message) {
-    let prefix = load_config("./config.json")
-        .expect("Failed to load config.json")
-        .discord_prefix;
+use redis::{AsyncCommands, RedisResult};
+use serde::Serialize;
+
+/// Discord message relayed to Twitch on the `discord_messages` channel.
+#[derive(Serialize)]
+struct BridgedMessage<'a> {
+    /// Twitch channel to post in, the Twitch service's own channel if `None`.
+    channel: Option<&'a str>,
+    content: &'a str,
+    author: &'a str,
+}
 
-    if msg.author.bot || msg.content.starts_with(&prefix) {
+#[tracing::instrument(
+    skip_all,
+    fields(channel = %msg.channel_id, user = %msg.author.id, message_id = %msg.id)
+)]
+pub async fn message_create(msg: Message, data: &Data) {
+    let config = data.settings.config(msg.guild_id).await;
+    if msg.author.bot || msg.content.starts_with(&config.discord_prefix) {
         return;
     }
 
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a TWITCH_BOT_USERNAME environment variable.");
-
-    if msg.channel_id == channel.parse::<u64>().unwrap() {
-        let redis_url = std::env::var("REDIS_URL").unwrap_or("redis://redis:6379".into());
+    if config.bridge_channel == Some(msg.channel_id) && config.feature_enabled("bridge") {
+        let mut con = data.redis.clone();
 
-        let client = match Client::open(redis_url) {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Failed to connect to Redis: {e}");
-                return;
-            }
-        };
-
-        let mut con = match client.get_multiplexed_async_connection().await {
-            Ok(c) => c,
+        let redis_channel_name = "discord_messages";
+        let message_to_publish = match serde_json::to_string(&BridgedMessage {
+            channel: config.twitch_channel.as_deref(),
+            content: &msg.content,
+            author: &msg.author.name,
+        }) {
+            Ok(message) => message,
             Err(e) => {
-                eprintln!("Failed to get Redis connection: {e}");
+                tracing::error!(error = %e, "Failed to encode Discord message");
                 return;
             }
         };
 
-        let redis_channel_name = "discord_messages";
-        let message_to_publish = format!("{}|{}", msg.content, msg.author.name);
-
         let result: RedisResult<()> = con.publish(redis_channel_name, &message_to_publish).await;
 
         if let Err(e) = result {
-            eprintln!("Failed to publish message to Redis: {e}");
+            tracing::error!(error = %e, "Failed to publish Discord message");
         } else {
-            println!("Successfully published message to Redis channel '{redis_channel_name}'",);
+            data.stats.relayed_to_twitch();
+            tracing::debug!(
+                redis_channel = redis_channel_name,
+                message = logging::content(&msg.content),
+                "Relayed Discord message"
+            );
         }
     }
 }
@@ -1,7 +1,15 @@
-use poise::serenity_prelude::{self as serenity, futures::StreamExt};
+use std::sync::Arc;
+
+use poise::serenity_prelude::{self as serenity, Message, futures::StreamExt};
 use redis::RedisResult;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tracing::Instrument;
+
+use crate::{
+    Error, chatters::Chatters, config::Config, guild_settings::GuildSettings, links::Links,
+    logging, rolesync::RoleSync, shutdown::Shutdown, stats::BridgeStats,
+};
 
 mod anongiftpaidupdate;
 mod cheer;
@@ -61,6 +69,34 @@ pub enum TwitchEventData {
     },
 }
 
+impl TwitchEventData {
+    /// Twitch channel the event happened in.
+    pub fn channel(&self) -> &str {
+        match self {
+            TwitchEventData::AnonGiftPaidUpdate { channel, .. }
+            | TwitchEventData::Cheer { channel, .. }
+            | TwitchEventData::Message { channel, .. }
+            | TwitchEventData::Raided { channel, .. }
+            | TwitchEventData::Resub { channel, .. }
+            | TwitchEventData::SubGift { channel, .. }
+            | TwitchEventData::Subscription { channel, .. } => channel,
+        }
+    }
+
+    /// Login of the Twitch user behind the event, if any.
+    pub fn user(&self) -> Option<&str> {
+        match self {
+            TwitchEventData::AnonGiftPaidUpdate { userstate, .. }
+            | TwitchEventData::Cheer { userstate, .. } => userstate["username"].as_str(),
+            TwitchEventData::Message { tags, .. } => tags["username"].as_str(),
+            TwitchEventData::Raided { username, .. }
+            | TwitchEventData::Resub { username, .. }
+            | TwitchEventData::SubGift { username, .. }
+            | TwitchEventData::Subscription { username, .. } => Some(username),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TwitchEvent {
     #[serde(rename = "eventType")]
@@ -68,54 +104,135 @@ pub struct TwitchEvent {
     pub data: TwitchEventData,
 }
 
-pub async fn start_redis_listener(http_client: serenity::Http, redis_url: &str) -> RedisResult<()> {
-    let client = redis::Client::open(redis_url)?;
+/// Posts a message, rendered with each guild's settings, to every bridge channel.
+///
+/// Fails only if no bridge could be reached, the other failures are logged.
+async fn relay(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    render: impl Fn(&Config) -> String,
+) -> Result<Vec<Message>, Error> {
+    let mut posted = vec![];
+    let mut failure = None;
+    for config in bridges {
+        let Some(channel) = config.bridge_channel else {
+            continue;
+        };
+        match channel.say(http_client, render(config)).await {
+            Ok(message) => posted.push(message),
+            Err(e) => {
+                tracing::warn!(channel = %channel, error = %e, "Failed to post to a bridge channel");
+                failure = Some(e);
+            }
+        }
+    }
+
+    match failure {
+        Some(e) if posted.is_empty() => Err(e.into()),
+        _ => Ok(posted),
+    }
+}
+
+pub async fn start_redis_listener(
+    http_client: serenity::Http,
+    settings: GuildSettings,
+    links: Links,
+    rolesync: RoleSync,
+    chatters: Chatters,
+    stats: Arc<BridgeStats>,
+    shutdown: Shutdown,
+) -> RedisResult<()> {
+    let client = redis::Client::open(settings.config(None).await.redis_url.as_str())?;
     let mut conn = client.get_async_pubsub().await?;
 
     conn.subscribe("twitch_events").await?;
     let mut pubsub_stream = conn.on_message();
+    stats.set_listener_alive(true);
 
-    println!("Listening for Redis messages on 'twitch_events' channel...");
+    tracing::info!(channel = "twitch_events", "Listening for Twitch events");
 
-    while let Some(msg) = pubsub_stream.next().await {
+    let token = shutdown.token();
+    loop {
+        let msg = tokio::select! {
+            msg = pubsub_stream.next() => msg,
+            _ = token.cancelled() => {
+                tracing::info!("Stopped listening for Twitch events");
+                break;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+        // The event being relayed is finished before the process exits.
+        let _task = shutdown.track();
         let payload: String = msg.get_payload()?;
-        println!("[Redis Listener] Received message");
 
-        if let Ok(event) = serde_json::from_str::<TwitchEvent>(&payload) {
-            println!(
-                "[Redis Listener] Parsed Event: {}",
-                event.event_type.as_str()
-            );
+        let event = match serde_json::from_str::<TwitchEvent>(&payload) {
+            Ok(event) => event,
+            Err(e) => {
+                stats.parse_failed();
+                tracing::warn!(
+                    error = %e,
+                    payload = logging::content(&payload),
+                    "Failed to parse Twitch event"
+                );
+                continue;
+            }
+        };
+
+        let event_type = event.event_type.clone();
+        let span = tracing::info_span!(
+            "bus_event",
+            event_type = %event_type,
+            channel = event.data.channel(),
+            user = event.data.user(),
+            relay_id = tracing::field::Empty,
+        );
+
+        async {
+            tracing::debug!("Received Twitch event");
+            let mut bridges = settings.bridges(&http_client, event.data.channel()).await;
+            bridges.retain(|config| config.event_enabled(&event_type));
+            // Chat messages also carry `!link` codes and badges, the handler runs without bridges.
+            if event_type != "message" && bridges.is_empty() {
+                tracing::debug!("No bridge relays this event");
+                return;
+            }
+            let started = stats.start_event(&event_type).await;
 
-            match event.event_type.as_str() {
+            let result = match event.event_type.as_str() {
                 "anongiftpaidupdate" => {
-                    let _ = anongiftpaidupdate::anongiftpaidupdate_event(&http_client, event).await;
-                }
-                "cheer" => {
-                    let _ = cheer::cheer_event(&http_client, event).await;
+                    anongiftpaidupdate::anongiftpaidupdate_event(&http_client, &bridges, event)
+                        .await
                 }
+                "cheer" => cheer::cheer_event(&http_client, &bridges, event).await,
                 "message" => {
-                    let _ = message::message_event(&http_client, event).await;
-                }
-                "raided" => {
-                    let _ = raided::raided_event(&http_client, event).await;
-                }
-                "resub" => {
-                    let _ = resub::resub_event(&http_client, event).await;
-                }
-                "subgift" => {
-                    let _ = subgift::subgift_event(&http_client, event).await;
+                    message::message_event(
+                        &http_client,
+                        &bridges,
+                        &links,
+                        &rolesync,
+                        &chatters,
+                        event,
+                    )
+                    .await
                 }
+                "raided" => raided::raided_event(&http_client, &bridges, event).await,
+                "resub" => resub::resub_event(&http_client, &bridges, &rolesync, event).await,
+                "subgift" => subgift::subgift_event(&http_client, &bridges, &rolesync, event).await,
                 "subscription" => {
-                    let _ = subscription::subgift_event(&http_client, event).await;
-                }
-                _ => {
-                    println!("Unknown event detected");
+                    subscription::subgift_event(&http_client, &bridges, &rolesync, event).await
                 }
+                event_type => Err(Error::Event(format!("Unknown event type `{event_type}`"))),
+            };
+
+            if let Err(e) = &result {
+                tracing::warn!(kind = e.kind(), error = %e, "Failed to relay Twitch event");
             }
-        } else {
-            eprintln!("[Redis Listener] Failed to parse JSON: {payload}");
+            stats.finish_event(&event_type, started, &result);
         }
+        .instrument(span)
+        .await;
     }
 
     Ok(())
@@ -1,11 +1,14 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
+
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData};
+use poise::serenity_prelude as serenity;
 
 pub async fn anongiftpaidupdate_event(
     http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
     event: TwitchEvent,
-) -> Result<(), String> {
+) -> Result<(), Error> {
     let chan;
     let mut userstate;
     let gift_count;
@@ -20,26 +23,23 @@ pub async fn anongiftpaidupdate_event(
             userstate = u;
             gift_count = g;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Message'")),
-    };
-
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
+        _ => {
+            return Err(Error::event(
+                "TwitchEventData is not of type 'AnonGiftPaidUpdate'",
+            ));
+        }
     };
 
     let dn = userstate["display-name"].take();
     let display_name = dn.as_str().unwrap_or("Error: No Name");
 
-    let msg = format!(
-        "### 🎉 A new subscriber! Anonymous user gifted `{display_name}` a sub to `{chan}`! (Gift Count: `{gift_count}`)"
-    );
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
-    }
+    relay(http_client, bridges, |config| {
+        config.templates.anon_gift.render(&[
+            ("channel", &chan),
+            ("user", display_name),
+            ("gift_count", &gift_count.to_string()),
+        ])
+    })
+    .await?;
     Ok(())
 }
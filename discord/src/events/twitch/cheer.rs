@@ -1,8 +1,14 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn cheer_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData};
+use poise::serenity_prelude as serenity;
+
+pub async fn cheer_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let mut userstate;
     let message;
@@ -17,15 +23,7 @@ pub async fn cheer_event(http_client: &serenity::Http, event: TwitchEvent) -> Re
             userstate = u;
             message = m;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Message'")),
-    };
-
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
+        _ => return Err(Error::event("TwitchEventData is not of type 'Cheer'")),
     };
 
     let dn = userstate["display-name"].take();
@@ -34,12 +32,14 @@ pub async fn cheer_event(http_client: &serenity::Http, event: TwitchEvent) -> Re
     let b = userstate["bits"].take();
     let bits = b.as_str().unwrap_or("Bits Error");
 
-    let msg = format!(
-        "### ✨ `{display_name}` just cheered with __{bits}__ bits to `{chan}`! Message: \"`{message}`\""
-    );
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
-    }
+    relay(http_client, bridges, |config| {
+        config.templates.cheer.render(&[
+            ("channel", &chan),
+            ("user", display_name),
+            ("bits", bits),
+            ("message", &message),
+        ])
+    })
+    .await?;
     Ok(())
 }
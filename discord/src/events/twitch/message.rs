@@ -1,8 +1,59 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn message_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{
+    Error,
+    chatters::{Chatters, RelayedMessage},
+    config::Config,
+    events::twitch::TwitchEventData,
+    links::{Links, TwitchAccount, link_code},
+    logging,
+    rolesync::RoleSync,
+};
+use poise::serenity_prelude::{self as serenity, CreateMessage};
+use serde_json::Value;
+
+async fn verify_link(
+    http_client: &serenity::Http,
+    links: &Links,
+    rolesync: &RoleSync,
+    tags: &Value,
+    code: &str,
+) -> Result<(), Error> {
+    let account = TwitchAccount {
+        id: tags["user-id"].as_str().unwrap_or_default().into(),
+        login: tags["username"].as_str().unwrap_or_default().into(),
+        display_name: tags["display-name"].as_str().unwrap_or_default().into(),
+    };
+    if account.id.is_empty() || account.login.is_empty() {
+        return Err(Error::event("Link attempt without a Twitch user id"));
+    }
+
+    let Some((discord_id, displaced)) = links.redeem_code(code, &account).await? else {
+        return Ok(());
+    };
+    for user_id in displaced {
+        rolesync.forget(user_id).await?;
+    }
+
+    let msg = format!(
+        "✅ Your Discord account is now linked to the Twitch account `{}`.",
+        account.display_name
+    );
+    discord_id
+        .direct_message(http_client, CreateMessage::new().content(msg))
+        .await?;
+    Ok(())
+}
+
+pub async fn message_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    links: &Links,
+    rolesync: &RoleSync,
+    chatters: &Chatters,
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let mut tags;
     let message;
@@ -21,15 +72,34 @@ pub async fn message_event(http_client: &serenity::Http, event: TwitchEvent) ->
             tags = t;
             message = m;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Message'")),
+        _ => return Err(Error::event("TwitchEventData is not of type 'Message'")),
     };
 
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
+    if let Some(code) = message.as_deref().and_then(link_code) {
+        return verify_link(http_client, links, rolesync, &tags, code).await;
+    }
+
+    if let Some(login) = tags["username"].as_str()
+        && let Err(e) = chatters.record(login).await
+    {
+        tracing::warn!(error = %e, "Failed to record Twitch chatter");
+    }
+
+    if let Some(user_id) = tags["user-id"].as_str()
+        && let Err(e) = rolesync
+            .observe_badges(http_client, user_id, &tags["badges"])
+            .await
+    {
+        tracing::warn!(error = %e, "Failed to sync badge roles");
+    }
+
+    if bridges.is_empty() {
+        return Ok(());
+    }
+
+    let linked = match tags["user-id"].as_str() {
+        Some(id) => links.discord_user(id).await.ok().flatten().is_some(),
+        None => false,
     };
 
     let dn = tags["display-name"].take();
@@ -40,10 +110,44 @@ pub async fn message_event(http_client: &serenity::Http, event: TwitchEvent) ->
         None => "No message".into(),
     };
 
-    let msg = format!("**(`{chan}`)** `{display_name}`: {msg}");
+    let relayed = RelayedMessage {
+        twitch_user_id: tags["user-id"].as_str().unwrap_or_default().into(),
+        login: tags["username"].as_str().unwrap_or_default().into(),
+        display_name: display_name.into(),
+        message_id: tags["id"].as_str().unwrap_or_default().into(),
+        message: msg.clone(),
+        timestamp: tags["tmi-sent-ts"]
+            .as_str()
+            .and_then(|ts| ts.parse().ok())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+    };
+
+    let badge = if linked { " 🔗" } else { "" };
+    let posted = relay(http_client, bridges, |config| {
+        config.templates.message.render(&[
+            ("channel", &chan),
+            ("user", display_name),
+            ("badge", badge),
+            ("message", &msg),
+        ])
+    })
+    .await?;
+    if let Some(first) = posted.first() {
+        tracing::Span::current().record("relay_id", first.id.get());
+    }
+    tracing::debug!(
+        message = logging::content(&relayed.message),
+        bridges = posted.len(),
+        "Relayed Twitch message"
+    );
 
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
+    if relayed.login.is_empty() {
+        return Ok(());
+    }
+    for message in &posted {
+        if let Err(e) = chatters.record_relay(message.id, &relayed).await {
+            tracing::warn!(error = %e, "Failed to record relayed message");
+        }
     }
     Ok(())
 }
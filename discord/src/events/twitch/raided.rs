@@ -1,8 +1,14 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn raided_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData};
+use poise::serenity_prelude as serenity;
+
+pub async fn raided_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let username;
     let viewers;
@@ -17,21 +23,16 @@ pub async fn raided_event(http_client: &serenity::Http, event: TwitchEvent) -> R
             username = u;
             viewers = v;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Resub'")),
-    };
-
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
+        _ => return Err(Error::event("TwitchEventData is not of type 'Raided'")),
     };
 
-    let msg = format!("### 🚀 `{username}` started a raid with __{viewers}__ viewers to `{chan}`!");
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
-    }
+    relay(http_client, bridges, |config| {
+        config.templates.raided.render(&[
+            ("channel", &chan),
+            ("user", &username),
+            ("viewers", &viewers.to_string()),
+        ])
+    })
+    .await?;
     Ok(())
 }
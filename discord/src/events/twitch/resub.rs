@@ -1,8 +1,15 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn resub_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData, rolesync::RoleSync};
+use poise::serenity_prelude as serenity;
+
+pub async fn resub_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    rolesync: &RoleSync,
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let username;
     let message;
@@ -21,27 +28,27 @@ pub async fn resub_event(http_client: &serenity::Http, event: TwitchEvent) -> Re
             message = me;
             userstate = us;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Resub'")),
+        _ => return Err(Error::event("TwitchEventData is not of type 'Resub'")),
     };
 
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
-    };
+    if let Err(e) = rolesync
+        .observe_subscription(http_client, userstate["user-id"].as_str(), &username)
+        .await
+    {
+        tracing::warn!(error = %e, "Failed to sync resub roles");
+    }
 
     let cm = userstate["msg-param-cumulative-months"].take();
     let cumulative_months = cm.as_str().unwrap_or("Error: No Cumulative Months");
 
-    let msg = format!(
-        "### ✨ Re-sub from `{username}`! This is their __{cumulative_months}__ month in a row subscribed to `{chan}`! \"`{}`\"",
-        message.unwrap_or("".into())
-    );
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
-    }
+    relay(http_client, bridges, |config| {
+        config.templates.resub.render(&[
+            ("channel", &chan),
+            ("user", &username),
+            ("months", cumulative_months),
+            ("message", message.as_deref().unwrap_or_default()),
+        ])
+    })
+    .await?;
     Ok(())
 }
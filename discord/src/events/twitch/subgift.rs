@@ -1,11 +1,19 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn subgift_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData, rolesync::RoleSync};
+use poise::serenity_prelude as serenity;
+
+pub async fn subgift_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    rolesync: &RoleSync,
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let username;
     let recipient;
+    let tags;
 
     match event.data {
         TwitchEventData::SubGift {
@@ -13,27 +21,36 @@ pub async fn subgift_event(http_client: &serenity::Http, event: TwitchEvent) ->
             username: u,
             streak_months: _,
             recipient: r,
-            tags: _,
+            tags: t,
         } => {
             chan = c;
             username = u;
             recipient = r;
+            tags = t;
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Resub'")),
-    };
-
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
+        _ => return Err(Error::event("TwitchEventData is not of type 'SubGift'")),
     };
 
-    let msg = format!("### 🎁 `{username}` just gifted `{recipient}` a sub to `{chan}`!",);
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
+    if let Err(e) = rolesync
+        .observe_subscription(
+            http_client,
+            tags["msg-param-recipient-id"].as_str(),
+            tags["msg-param-recipient-user-name"]
+                .as_str()
+                .unwrap_or(&recipient),
+        )
+        .await
+    {
+        tracing::warn!(error = %e, "Failed to sync subgift roles");
     }
+
+    relay(http_client, bridges, |config| {
+        config.templates.subgift.render(&[
+            ("channel", &chan),
+            ("user", &username),
+            ("recipient", &recipient),
+        ])
+    })
+    .await?;
     Ok(())
 }
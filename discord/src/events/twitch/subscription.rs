@@ -1,42 +1,54 @@
-use super::TwitchEvent;
-use crate::events::twitch::TwitchEventData;
-use poise::serenity_prelude::{self as serenity, ChannelId};
+use std::sync::Arc;
 
-pub async fn subgift_event(http_client: &serenity::Http, event: TwitchEvent) -> Result<(), String> {
+use super::{TwitchEvent, relay};
+use crate::{Error, config::Config, events::twitch::TwitchEventData, rolesync::RoleSync};
+use poise::serenity_prelude as serenity;
+
+pub async fn subgift_event(
+    http_client: &serenity::Http,
+    bridges: &[Arc<Config>],
+    rolesync: &RoleSync,
+    event: TwitchEvent,
+) -> Result<(), Error> {
     let chan;
     let username;
     let message;
+    let tags;
 
     match event.data {
         TwitchEventData::Subscription {
             channel: c,
             username: u,
             methods: _,
-            tags: _,
+            tags: t,
             message: m,
         } => {
             chan = c;
             username = u;
             message = m;
+            tags = t;
+        }
+        _ => {
+            return Err(Error::event(
+                "TwitchEventData is not of type 'Subscription'",
+            ));
         }
-        _ => return Err(String::from("TwitchEventData is not of type 'Resub'")),
-    };
-
-    let channel = std::env::var("DISCORD_CHANNEL_ID")
-        .expect("Expected a DISCORD_CHANNEL_ID environment variable.");
-    let id: u64 = channel.parse().unwrap();
-    let chann_id = ChannelId::new(id);
-    if let Err(e) = http_client.get_channel(chann_id).await {
-        return Err(format!("{e:?}"));
     };
 
-    let msg = format!(
-        "### 🎉 A new subscriber! `{username}` just subscribed to `{chan}`! \"`{}`\"",
-        message.unwrap_or("".into())
-    );
-
-    if let Err(e) = chann_id.say(&http_client, msg).await {
-        return Err(format!("{e:?}"));
+    if let Err(e) = rolesync
+        .observe_subscription(http_client, tags["user-id"].as_str(), &username)
+        .await
+    {
+        tracing::warn!(error = %e, "Failed to sync subscription roles");
     }
+
+    relay(http_client, bridges, |config| {
+        config.templates.subscription.render(&[
+            ("channel", &chan),
+            ("user", &username),
+            ("message", message.as_deref().unwrap_or_default()),
+        ])
+    })
+    .await?;
     Ok(())
 }
@@ -0,0 +1,388 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+
+use poise::serenity_prelude::{self as serenity, ChannelId, GuildId, UserId};
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    Error,
+    config::{Config, EVENTS, FEATURES, SharedConfig, Templates},
+    rpc::RpcClient,
+    shutdown::Shutdown,
+};
+
+const AUDIT_LEN: isize = 50;
+/// Guilds with at least one override.
+const CONFIGURED_KEY: &str = "guild:configured";
+/// Twitch channels the Twitch service joins on startup, next to `TWITCH_CHANNEL`.
+const TWITCH_CHANNELS_KEY: &str = "twitch:channels";
+/// How long the settings of a guild the bot left are kept in case it comes back.
+const LEFT_GUILD_TTL: i64 = 30 * 24 * 60 * 60;
+
+/// Names of the settings a guild can override, `event.*` and `template.*` expanded.
+pub fn setting_names() -> Vec<String> {
+    let mut names = vec![
+        String::from("prefix"),
+        String::from("color"),
+        String::from("bridge_channel"),
+        String::from("twitch_channel"),
+        String::from("season_days"),
+        String::from("betting_turns"),
+    ];
+    names.extend(FEATURES.iter().map(|feature| format!("feature.{feature}")));
+    names.extend(EVENTS.iter().map(|event| format!("event.{event}")));
+    names.extend(Templates::names().map(|name| format!("template.{name}")));
+    names
+}
+
+/// A change made with `/config`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub user_id: UserId,
+    pub setting: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub timestamp: i64,
+}
+
+fn overrides_key(guild_id: GuildId) -> String {
+    format!("guild:config:{guild_id}")
+}
+
+fn audit_key(guild_id: GuildId) -> String {
+    format!("guild:config:audit:{guild_id}")
+}
+
+/// Bridge channel from `config.json` and the guild it belongs to.
+type HomeGuild = Option<(ChannelId, Option<GuildId>)>;
+
+struct Cached {
+    overrides: HashMap<String, String>,
+    /// Global configuration `merged` was built from, rebuilt when `config.json` is reloaded.
+    base: Arc<Config>,
+    merged: Arc<Config>,
+}
+
+/// Per-guild overrides of the configuration, stored in Redis.
+///
+/// Each guild sees `config.json` with its own overrides applied on top, except for the bridge
+/// channel: only the guild it belongs to inherits it, the others pick their own. Twitch events
+/// are relayed to every guild with a bridge channel, including the one `config.json` points to.
+#[derive(Clone)]
+pub struct GuildSettings {
+    con: ConnectionManager,
+    config: SharedConfig,
+    cache: Arc<RwLock<HashMap<GuildId, Cached>>>,
+    home: Arc<RwLock<HomeGuild>>,
+}
+
+impl GuildSettings {
+    pub fn new(con: ConnectionManager, config: SharedConfig) -> Self {
+        Self {
+            con,
+            config,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+            home: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Configuration as seen by `guild_id`, or the global one outside of guilds.
+    pub async fn config(&self, guild_id: Option<GuildId>) -> Arc<Config> {
+        let base = self.config.get();
+        let Some(guild_id) = guild_id else {
+            return base;
+        };
+
+        if let Some(cached) = self.cache.read().await.get(&guild_id)
+            && Arc::ptr_eq(&cached.base, &base)
+        {
+            return cached.merged.clone();
+        }
+
+        let overrides = match self.cache.read().await.get(&guild_id) {
+            Some(cached) => cached.overrides.clone(),
+            None => match self.load(guild_id).await {
+                Ok(overrides) => overrides,
+                Err(e) => {
+                    tracing::warn!(guild = %guild_id, error = %e, "Failed to load guild settings");
+                    return base;
+                }
+            },
+        };
+        self.merge(guild_id, base, overrides).await
+    }
+
+    /// Configurations of the guilds events from the Twitch channel `channel` are relayed to.
+    pub async fn bridges(&self, http_client: &serenity::Http, channel: &str) -> Vec<Arc<Config>> {
+        let mut con = self.con.clone();
+        let mut guilds: HashSet<GuildId> = match con.smembers::<_, Vec<u64>>(CONFIGURED_KEY).await {
+            Ok(guilds) => guilds.into_iter().map(GuildId::new).collect(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to load configured guilds");
+                HashSet::new()
+            }
+        };
+
+        let mut configs = vec![match self.home(http_client).await {
+            Some(guild_id) => {
+                guilds.remove(&guild_id);
+                self.config(Some(guild_id)).await
+            }
+            None => self.config.get(),
+        }];
+        for guild_id in guilds {
+            configs.push(self.config(Some(guild_id)).await);
+        }
+
+        configs.retain(|config| config.bridges(channel));
+        // Each channel gets an event once, from the home guild first.
+        let mut channels = HashSet::new();
+        configs.retain(|config| channels.insert(config.bridge_channel));
+        configs
+    }
+
+    /// Guild the bridge channel from `config.json` belongs to, the only one that inherits it.
+    pub async fn home(&self, http_client: &serenity::Http) -> Option<GuildId> {
+        let channel = self.config.get().bridge_channel?;
+
+        let cached = *self.home.read().await;
+        match cached {
+            Some((cached, guild_id)) if cached == channel => guild_id,
+            _ => {
+                let guild_id = match http_client.get_channel(channel).await {
+                    Ok(channel) => channel.guild().map(|channel| channel.guild_id),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Failed to look up the bridge channel");
+                        return None;
+                    }
+                };
+                *self.home.write().await = Some((channel, guild_id));
+                // Guilds merged before this inherited the wrong bridge channel.
+                self.cache.write().await.clear();
+                guild_id
+            }
+        }
+    }
+
+    /// Restores the settings of a guild the bot was added back to.
+    pub async fn joined(&self, guild_id: GuildId) -> RedisResult<()> {
+        let mut con = self.con.clone();
+        let _: () = con.persist(overrides_key(guild_id)).await?;
+        let _: () = con.persist(audit_key(guild_id)).await?;
+
+        if !self.load(guild_id).await?.is_empty() {
+            let _: () = con.sadd(CONFIGURED_KEY, guild_id.get()).await?;
+        }
+        self.cache.write().await.remove(&guild_id);
+        Ok(())
+    }
+
+    /// Stops relaying to a guild the bot was removed from, its settings expire later.
+    pub async fn left(&self, guild_id: GuildId) -> RedisResult<()> {
+        let mut con = self.con.clone();
+        let _: () = con.srem(CONFIGURED_KEY, guild_id.get()).await?;
+        let _: () = con.expire(overrides_key(guild_id), LEFT_GUILD_TTL).await?;
+        let _: () = con.expire(audit_key(guild_id), LEFT_GUILD_TTL).await?;
+
+        self.cache.write().await.remove(&guild_id);
+        Ok(())
+    }
+
+    /// Twitch channels used by `config.json` and the configured guilds.
+    pub async fn twitch_channels(&self) -> RedisResult<BTreeSet<String>> {
+        let mut con = self.con.clone();
+        let guilds: Vec<u64> = con.smembers(CONFIGURED_KEY).await?;
+
+        let mut channels = BTreeSet::new();
+        channels.extend(self.config.get().twitch_channel.clone());
+        for guild_id in guilds {
+            let config = self.config(Some(GuildId::new(guild_id))).await;
+            channels.extend(config.twitch_channel.clone());
+        }
+        Ok(channels)
+    }
+
+    /// Makes the Twitch service join the channels guilds use and leave the ones they stopped using.
+    pub async fn sync_twitch_channels(&self, rpc: &RpcClient, issued_by: &str) -> RedisResult<()> {
+        let wanted = self.twitch_channels().await?;
+
+        let mut con = self.con.clone();
+        let joined: BTreeSet<String> = con.smembers(TWITCH_CHANNELS_KEY).await?;
+
+        // Only channels the Twitch service answered for are recorded, the rest are retried
+        // on the next sync.
+        for channel in wanted.difference(&joined) {
+            match rpc.join(channel, issued_by).await {
+                Ok(()) => {
+                    let _: () = con.sadd(TWITCH_CHANNELS_KEY, channel).await?;
+                    tracing::info!(channel, "Joined Twitch channel");
+                }
+                Err(e) => tracing::warn!(channel, error = %e, "Failed to join Twitch channel"),
+            }
+        }
+        for channel in joined.difference(&wanted) {
+            match rpc.part(channel, issued_by).await {
+                Ok(()) => {
+                    let _: () = con.srem(TWITCH_CHANNELS_KEY, channel).await?;
+                    tracing::info!(channel, "Left Twitch channel");
+                }
+                Err(e) => tracing::warn!(channel, error = %e, "Failed to leave Twitch channel"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs [`GuildSettings::sync_twitch_channels`] in the background, every join or part
+    /// can take up to the RPC timeout.
+    pub fn spawn_twitch_sync(&self, shutdown: &Shutdown, rpc: RpcClient, issued_by: String) {
+        let settings = self.clone();
+        shutdown.spawn(async move {
+            if let Err(e) = settings.sync_twitch_channels(&rpc, &issued_by).await {
+                tracing::warn!(error = %e, "Failed to sync Twitch channels");
+            }
+        });
+    }
+
+    /// Overrides stored for `guild_id`.
+    pub async fn overrides(&self, guild_id: GuildId) -> RedisResult<HashMap<String, String>> {
+        if let Some(cached) = self.cache.read().await.get(&guild_id) {
+            return Ok(cached.overrides.clone());
+        }
+        self.load(guild_id).await
+    }
+
+    /// Validates and stores an override, returning the previous one.
+    pub async fn set(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        setting: &str,
+        value: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut check = (*self.config(Some(guild_id)).await).clone();
+        check.apply(setting, value).map_err(Error::User)?;
+
+        let mut con = self.con.clone();
+        let old: Option<String> = con.hget(overrides_key(guild_id), setting).await?;
+        let _: () = con.hset(overrides_key(guild_id), setting, value).await?;
+        let _: () = con.sadd(CONFIGURED_KEY, guild_id.get()).await?;
+
+        self.audit(guild_id, user_id, setting, old.clone(), Some(value.into()))
+            .await?;
+        self.refresh(guild_id).await?;
+        Ok(old)
+    }
+
+    /// Removes an override so the guild falls back to `config.json`, returning it.
+    pub async fn reset(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        setting: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut con = self.con.clone();
+        let old: Option<String> = con.hget(overrides_key(guild_id), setting).await?;
+        if old.is_none() {
+            return Ok(None);
+        }
+        let _: () = con.hdel(overrides_key(guild_id), setting).await?;
+        let remaining: usize = con.hlen(overrides_key(guild_id)).await?;
+        if remaining == 0 {
+            let _: () = con.srem(CONFIGURED_KEY, guild_id.get()).await?;
+        }
+
+        self.audit(guild_id, user_id, setting, old.clone(), None)
+            .await?;
+        self.refresh(guild_id).await?;
+        Ok(old)
+    }
+
+    /// Most recent changes first.
+    pub async fn audit_log(&self, guild_id: GuildId, limit: isize) -> RedisResult<Vec<AuditEntry>> {
+        let mut con = self.con.clone();
+
+        let entries: Vec<String> = con.lrange(audit_key(guild_id), 0, limit - 1).await?;
+        Ok(entries
+            .iter()
+            .filter_map(|entry| serde_json::from_str(entry).ok())
+            .collect())
+    }
+
+    async fn audit(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        setting: &str,
+        old: Option<String>,
+        new: Option<String>,
+    ) -> RedisResult<()> {
+        let entry = AuditEntry {
+            user_id,
+            setting: setting.into(),
+            old,
+            new,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+        tracing::info!(
+            guild = %guild_id,
+            user = %user_id,
+            setting,
+            old = ?entry.old,
+            new = ?entry.new,
+            "Guild setting changed"
+        );
+
+        let mut con = self.con.clone();
+        let payload = serde_json::to_string(&entry).unwrap_or_default();
+        let _: () = con.lpush(audit_key(guild_id), payload).await?;
+        let _: () = con.ltrim(audit_key(guild_id), 0, AUDIT_LEN - 1).await?;
+        Ok(())
+    }
+
+    async fn load(&self, guild_id: GuildId) -> RedisResult<HashMap<String, String>> {
+        let mut con = self.con.clone();
+        con.hgetall(overrides_key(guild_id)).await
+    }
+
+    async fn refresh(&self, guild_id: GuildId) -> RedisResult<()> {
+        let overrides = self.load(guild_id).await?;
+        self.merge(guild_id, self.config.get(), overrides).await;
+        Ok(())
+    }
+
+    async fn merge(
+        &self,
+        guild_id: GuildId,
+        base: Arc<Config>,
+        overrides: HashMap<String, String>,
+    ) -> Arc<Config> {
+        let (mut merged, problems) = base.with_overrides(&overrides);
+        for problem in problems {
+            tracing::warn!(guild = %guild_id, problem, "Ignoring invalid guild setting");
+        }
+        let home = *self.home.read().await;
+        let inherits = matches!(
+            (home, base.bridge_channel),
+            (Some((channel, Some(home_guild))), Some(bridge_channel))
+                if channel == bridge_channel && home_guild == guild_id
+        );
+        if !inherits && !overrides.contains_key("bridge_channel") {
+            merged.bridge_channel = None;
+        }
+
+        let merged = Arc::new(merged);
+        self.cache.write().await.insert(
+            guild_id,
+            Cached {
+                overrides,
+                base,
+                merged: merged.clone(),
+            },
+        );
+        merged
+    }
+}
@@ -0,0 +1,196 @@
+use poise::serenity_prelude::UserId;
+use rand::Rng;
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+
+/// Chat command a Twitch user types, followed by the code, to complete a link.
+pub const LINK_COMMAND: &str = "!link";
+
+const CODE_TTL_SECS: u64 = 600;
+const CODE_LEN: usize = 6;
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// The code of a chat message that is exactly [`LINK_COMMAND`] followed by a link code.
+///
+/// Codes aren't case sensitive, other messages starting with `!link` are plain chat.
+pub fn link_code(message: &str) -> Option<&str> {
+    let mut words = message.split_whitespace();
+    let (Some(LINK_COMMAND), Some(code), None) = (words.next(), words.next(), words.next()) else {
+        return None;
+    };
+    let valid = code.len() == CODE_LEN
+        && code
+            .bytes()
+            .all(|c| CODE_ALPHABET.contains(&c.to_ascii_uppercase()));
+    valid.then_some(code)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwitchAccount {
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+}
+
+/// Redis-backed mapping between Discord users and their verified Twitch accounts.
+#[derive(Clone)]
+pub struct Links {
+    con: ConnectionManager,
+}
+
+fn code_key(code: &str) -> String {
+    format!("link:code:{code}")
+}
+
+fn pending_key(discord_id: UserId) -> String {
+    format!("link:pending:{discord_id}")
+}
+
+fn discord_key(discord_id: UserId) -> String {
+    format!("link:discord:{discord_id}")
+}
+
+fn twitch_key(twitch_id: &str) -> String {
+    format!("link:twitch:{twitch_id}")
+}
+
+fn login_key(login: &str) -> String {
+    format!("link:login:{}", login.to_lowercase())
+}
+
+impl Links {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    /// Issues a fresh one-time code for `discord_id`, invalidating any previous one.
+    pub async fn create_code(&self, discord_id: UserId) -> RedisResult<String> {
+        let mut con = self.con.clone();
+
+        let previous: Option<String> = con.get(pending_key(discord_id)).await?;
+        if let Some(previous) = previous {
+            let _: () = con.del(code_key(&previous)).await?;
+        }
+
+        let code: String = {
+            let mut rng = rand::rng();
+            (0..CODE_LEN)
+                .map(|_| CODE_ALPHABET[rng.random_range(0..CODE_ALPHABET.len())] as char)
+                .collect()
+        };
+
+        let _: () = con
+            .set_ex(code_key(&code), discord_id.get(), CODE_TTL_SECS)
+            .await?;
+        let _: () = con
+            .set_ex(pending_key(discord_id), &code, CODE_TTL_SECS)
+            .await?;
+
+        Ok(code)
+    }
+
+    /// Consumes `code` and stores the link if it was valid, returning the Discord user it belonged to.
+    ///
+    /// Also returns the Discord users whose link to another account was replaced, they
+    /// lose the roles that link gave them.
+    pub async fn redeem_code(
+        &self,
+        code: &str,
+        account: &TwitchAccount,
+    ) -> RedisResult<Option<(UserId, Vec<UserId>)>> {
+        let mut con = self.con.clone();
+
+        let code = code.trim().to_uppercase();
+        let discord_id: Option<u64> = con.get_del(code_key(&code)).await?;
+        let Some(discord_id) = discord_id.filter(|id| *id != 0).map(UserId::new) else {
+            return Ok(None);
+        };
+        let _: () = con.del(pending_key(discord_id)).await?;
+
+        let mut displaced = vec![];
+        if let Some(previous) = self.unlink(discord_id).await?
+            && previous.id != account.id
+        {
+            displaced.push(discord_id);
+        }
+        let previous_owner: Option<u64> = con.get(twitch_key(&account.id)).await?;
+        if let Some(previous_owner) = previous_owner.filter(|id| *id != 0).map(UserId::new) {
+            self.unlink(previous_owner).await?;
+            displaced.push(previous_owner);
+        }
+
+        let _: () = con
+            .set(
+                discord_key(discord_id),
+                serde_json::to_string(account).unwrap_or_default(),
+            )
+            .await?;
+        let _: () = con.set(twitch_key(&account.id), discord_id.get()).await?;
+        let _: () = con.set(login_key(&account.login), discord_id.get()).await?;
+
+        Ok(Some((discord_id, displaced)))
+    }
+
+    /// Removes the link of `discord_id`, returning the Twitch account it pointed to.
+    pub async fn unlink(&self, discord_id: UserId) -> RedisResult<Option<TwitchAccount>> {
+        let mut con = self.con.clone();
+
+        let Some(account) = self.twitch_account(discord_id).await? else {
+            return Ok(None);
+        };
+
+        let _: () = con.del(discord_key(discord_id)).await?;
+        let _: () = con.del(twitch_key(&account.id)).await?;
+        let _: () = con.del(login_key(&account.login)).await?;
+
+        Ok(Some(account))
+    }
+
+    pub async fn twitch_account(&self, discord_id: UserId) -> RedisResult<Option<TwitchAccount>> {
+        let mut con = self.con.clone();
+
+        let raw: Option<String> = con.get(discord_key(discord_id)).await?;
+        Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+    }
+
+    pub async fn discord_user(&self, twitch_id: &str) -> RedisResult<Option<UserId>> {
+        let mut con = self.con.clone();
+
+        let id: Option<u64> = con.get(twitch_key(twitch_id)).await?;
+        Ok(id.filter(|id| *id != 0).map(UserId::new))
+    }
+
+    pub async fn discord_user_by_login(&self, login: &str) -> RedisResult<Option<UserId>> {
+        let mut con = self.con.clone();
+
+        let id: Option<u64> = con.get(login_key(login)).await?;
+        Ok(id.filter(|id| *id != 0).map(UserId::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_link_codes() {
+        assert_eq!(link_code("!link AB23CD"), Some("AB23CD"));
+        assert_eq!(link_code("  !link   ab23cd "), Some("ab23cd"));
+    }
+
+    #[test]
+    fn leaves_other_chat_alone() {
+        for message in [
+            "!link",
+            "!links in bio",
+            "!linked",
+            "!link pls",
+            "!link AB23CD thanks",
+            "!link AB23C0",
+            "!link AB23CDE",
+            "hey !link AB23CD",
+        ] {
+            assert_eq!(link_code(message), None, "{message}");
+        }
+    }
+}
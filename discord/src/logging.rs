@@ -0,0 +1,36 @@
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tracing_subscriber::EnvFilter;
+
+static REDACT_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+/// Installs the global tracing subscriber.
+///
+/// `RUST_LOG` filters the output (`info` by default), `LOG_FORMAT=json` switches to one JSON
+/// object per line and `LOG_REDACT_MESSAGES=true` hides the contents of bridged messages.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let json = env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+    let redact = env::var("LOG_REDACT_MESSAGES")
+        .is_ok_and(|redact| matches!(redact.to_lowercase().as_str(), "1" | "true" | "yes"));
+    REDACT_MESSAGES.store(redact, Ordering::Relaxed);
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+/// Message contents as they should appear in logs.
+pub fn content(message: &str) -> &str {
+    if REDACT_MESSAGES.load(Ordering::Relaxed) {
+        "[redacted]"
+    } else {
+        message
+    }
+}
@@ -1,53 +1,74 @@
-use poise::serenity_prelude::{self as serenity, EventHandler, UserId};
-use serde::Deserialize;
-use std::{collections::HashMap, env, fs, path::Path, sync::Arc};
-use tokio::sync::Mutex;
+use clap::Parser;
+use poise::serenity_prelude::{self as serenity, ChannelId, EventHandler, GuildId};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
+mod bets;
+mod chatters;
+mod cli;
 mod cmds;
+mod config;
+mod duels;
+mod error;
 mod events;
+mod guild_settings;
+mod links;
+mod logging;
+mod metrics;
+mod profiles;
+mod ratings;
+mod rolesync;
+mod rpc;
+mod shutdown;
+mod stats;
+mod template;
+mod wallets;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct GameState {
-    player1_id: UserId,
-    player2_id: UserId,
-    player1_hp: u32,
-    player2_hp: u32,
-    turn: UserId,
+    duel: duels::engine::Duel,
+    #[serde(default)]
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    /// Unix time the challenge expires or the current turn is forfeited.
+    deadline: i64,
+    /// Coins each player puts in, the winner takes both.
+    #[serde(default)]
+    wager: u64,
 }
 
 pub struct Data {
-    color: (u8, u8, u8),
-    active_duels: Arc<Mutex<HashMap<serenity::MessageId, GameState>>>,
+    settings: guild_settings::GuildSettings,
+    duels: duels::Duels,
+    profiles: profiles::Profiles,
+    ratings: ratings::Ratings,
+    duel_results: duels::DuelResults,
+    wallets: wallets::Wallets,
+    bets: bets::Bets,
+    links: links::Links,
+    rolesync: rolesync::RoleSync,
+    rpc: rpc::RpcClient,
+    chatters: chatters::Chatters,
+    redis: redis::aio::ConnectionManager,
+    stats: Arc<stats::BridgeStats>,
+    shutdown: shutdown::Shutdown,
 }
 
-pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub use error::Error;
 
-pub type Context<'a> = poise::Context<'a, Data, Error>;
+const CONFIG_PATH: &str = "./config.json";
 
-#[derive(Deserialize, Debug)]
-pub struct Config {
-    discord_prefix: String,
-    color: [u8; 3],
-}
+pub type Context<'a> = poise::Context<'a, Data, Error>;
 
-pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn std::error::Error>> {
-    let file_contents = fs::read_to_string(path)?;
-    let config: Config = serde_json::from_str(&file_contents)?;
-    Ok(config)
+struct Handler {
+    stats: Arc<stats::BridgeStats>,
 }
 
-struct Handler;
-
 #[poise::async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _ctx: serenity::Context, _data_about_bot: serenity::Ready) {
-        println!("Bot is ready!");
-    }
-
-    async fn message(&self, ctx: serenity::Context, new_message: serenity::Message) {
-        if new_message.author.id != ctx.cache.current_user().id {
-            events::discord::message_create::message_create(new_message).await;
-        }
+    async fn ready(&self, _ctx: serenity::Context, data_about_bot: serenity::Ready) {
+        self.stats.set_discord_ready(true);
+        tracing::info!(user = %data_about_bot.user.name, "Bot is ready");
     }
 }
 
@@ -57,41 +78,255 @@ async fn event_handler(
     framework: poise::FrameworkContext<'_, Data, Error>,
     _data: &Data,
 ) -> Result<(), Error> {
-    if let serenity::FullEvent::InteractionCreate { interaction } = event {
-        events::discord::interaction_create::interaction_create(
-            ctx,
-            interaction.clone(),
-            framework,
-            _data,
-        )
-        .await?;
+    let _task = _data.shutdown.track();
+    match event {
+        serenity::FullEvent::InteractionCreate { interaction } => {
+            events::discord::interaction_create::interaction_create(
+                ctx,
+                interaction.clone(),
+                framework,
+                _data,
+            )
+            .await?;
+        }
+        serenity::FullEvent::Message { new_message }
+            if new_message.author.id != ctx.cache.current_user().id =>
+        {
+            events::discord::message_create::message_create(new_message.clone(), _data).await;
+        }
+        serenity::FullEvent::GuildCreate { guild, is_new } => {
+            _data.settings.joined(guild.id).await?;
+            if *is_new == Some(true) {
+                tracing::info!(guild = %guild.id, name = %guild.name, members = guild.member_count, "Joined guild");
+                _data.settings.spawn_twitch_sync(
+                    &_data.shutdown,
+                    _data.rpc.clone(),
+                    String::from("discord"),
+                );
+            }
+        }
+        serenity::FullEvent::GuildDelete { incomplete, full } if !incomplete.unavailable => {
+            _data.settings.left(incomplete.id).await?;
+            tracing::info!(
+                guild = %incomplete.id,
+                name = ?full.as_ref().map(|guild| &guild.name),
+                "Left guild"
+            );
+            _data.settings.spawn_twitch_sync(
+                &_data.shutdown,
+                _data.rpc.clone(),
+                String::from("discord"),
+            );
+        }
+        _ => {}
     }
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    tracing_subscriber::fmt::init();
     dotenvy::dotenv().ok();
+    let cli = cli::Cli::parse();
+    logging::init();
+
+    if let Some(cli::Command::ListCommands) = cli.command {
+        cli::list_commands();
+        return Ok(());
+    }
+
+    let config = match config::Config::load(CONFIG_PATH) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    match cli.command.unwrap_or(cli::Command::Run) {
+        cli::Command::Run => run(config).await?,
+        cli::Command::Register { guild } => {
+            let guild = guild.map(GuildId::new);
+            let count = cli::register(&config, guild).await?;
+            match guild {
+                Some(guild_id) => println!("Registered {count} commands in guild {guild_id}."),
+                None => println!(
+                    "Registered {count} commands globally, they can take up to an hour to show up."
+                ),
+            }
+        }
+        cli::Command::Unregister { guild } => {
+            let guild = guild.map(GuildId::new);
+            cli::unregister(&config, guild).await?;
+            match guild {
+                Some(guild_id) => println!("Removed the commands of guild {guild_id}."),
+                None => println!("Removed the global commands."),
+            }
+        }
+        cli::Command::CheckConfig => cli::check_config(&config),
+        cli::Command::ListCommands => unreachable!("handled before loading the configuration"),
+        cli::Command::Simulate {
+            event,
+            channel,
+            user,
+            message,
+        } => {
+            let receivers = cli::simulate(&config, event, channel, &user, &message).await?;
+            println!("Published the event to {receivers} listener(s).");
+        }
+    }
+    Ok(())
+}
+
+/// Registers the commands where this build should have them.
+///
+/// Development builds use the test guild, where commands update instantly.
+async fn register_commands(
+    ctx: &serenity::Context,
+    commands: &[poise::Command<Data, Error>],
+    test_guild: Option<GuildId>,
+) -> Result<(), Error> {
+    match test_guild {
+        Some(guild_id) if cfg!(debug_assertions) => {
+            poise::builtins::register_in_guild(ctx, commands, guild_id).await?;
+            tracing::info!(guild = %guild_id, "Registered commands in the test guild");
+        }
+        _ => {
+            if cfg!(debug_assertions) {
+                tracing::warn!(
+                    "Registering commands globally, set DISCORD_TEST_GUILD to see changes instantly"
+                );
+            }
+            poise::builtins::register_globally(ctx, commands).await?;
+        }
+    }
+    Ok(())
+}
 
-    println!("Loading discord bot...");
+async fn run(
+    config: config::Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    tracing::info!("Loading discord bot...");
+    tracing::info!(
+        prefix = %config.discord_prefix,
+        bridge_channel = ?config.bridge_channel,
+        twitch_channel = ?config.twitch_channel,
+        twitch_mod_role = ?config.twitch_mod_role,
+        metrics_addr = ?config.metrics_addr,
+        "Configuration loaded"
+    );
 
-    let discord_token =
-        env::var("DISCORD_BOT_TOKEN").expect("Expected a DISCORD_BOT_TOKEN environment variable.");
+    let discord_token = config.discord_token.clone();
+    let test_guild = config.test_guild;
+    let shared_config = config::SharedConfig::new(config.clone());
+
+    let shutdown = shutdown::Shutdown::new();
+
+    let watch_token = shutdown.token();
+    let watch_config = shared_config.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = config::watch(CONFIG_PATH.into(), watch_config) => {}
+            _ = watch_token.cancelled() => {}
+        }
+    });
 
     let commands = cmds::get_all_commands();
 
-    let config = load_config("./config.json").expect("Failed to load config.json");
+    let redis_client = redis::Client::open(config.redis_url.as_str())?;
+    let redis_con = redis::aio::ConnectionManager::new(redis_client).await?;
+    let links = links::Links::new(redis_con.clone());
+    let rolesync = rolesync::RoleSync::new(redis_con.clone(), links.clone(), shared_config.clone());
+    let settings = guild_settings::GuildSettings::new(redis_con.clone(), shared_config.clone());
+    let rpc = rpc::RpcClient::new(redis_con.clone());
+    let chatters = chatters::Chatters::new(redis_con.clone());
+    let stats = Arc::new(stats::BridgeStats::new());
+    let listener_stats = stats.clone();
+    let handler_stats = stats.clone();
+    let duels = duels::Duels::load(redis_con.clone()).await?;
+    let restored = duels.count().await;
+    if restored > 0 {
+        tracing::info!(duels = restored, "Restored duels in progress");
+    }
+    let timeout_duels = duels.clone();
+    let profiles = profiles::Profiles::new(redis_con.clone());
+    let ratings = ratings::Ratings::new(redis_con.clone());
+    let wallets = wallets::Wallets::new(redis_con.clone());
+    let seeded = wallets.seed_grants().await?;
+    if seeded > 0 {
+        tracing::info!(wallets = seeded, "Marked existing wallets as granted");
+    }
+    let bets = bets::Bets::new(redis_con.clone());
+    let duel_results = duels::DuelResults::new(
+        redis_con.clone(),
+        profiles.clone(),
+        ratings.clone(),
+        wallets.clone(),
+        bets.clone(),
+        settings.clone(),
+    );
+    let timeout_results = duel_results.clone();
 
-    let color = (config.color[0], config.color[1], config.color[2]);
+    if let Some(addr) = config.metrics_addr {
+        let stats = stats.clone();
+        let duels = duels.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, stats, duels).await {
+                tracing::error!(error = %e, "Metrics server stopped");
+            }
+        });
+    }
+
+    let listener_chatters = chatters.clone();
+    let reply_rpc = rpc.clone();
+    let reply_redis_url = config.redis_url.clone();
+    let listener_settings = settings.clone();
+    let listener_links = links.clone();
+    let listener_rolesync = rolesync.clone();
+    let listener_shutdown = shutdown.clone();
+    let data_shutdown = shutdown.clone();
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             commands,
             prefix_options: poise::PrefixFrameworkOptions {
-                prefix: Some(config.discord_prefix),
+                dynamic_prefix: Some(|ctx| {
+                    Box::pin(async move {
+                        let config = ctx.data.settings.config(ctx.guild_id).await;
+                        Ok(Some(config.discord_prefix.clone()))
+                    })
+                }),
                 ..Default::default()
             },
+            pre_command: |ctx| {
+                Box::pin(async move {
+                    tracing::info!(
+                        command = %ctx.command().qualified_name,
+                        user = %ctx.author().id,
+                        guild = ?ctx.guild_id(),
+                        channel = %ctx.channel_id(),
+                        "Command invoked"
+                    );
+                    ctx.data()
+                        .stats
+                        .metrics()
+                        .command_invocations
+                        .with_label_values(&[&ctx.command().qualified_name])
+                        .inc();
+                    ctx.data().shutdown.command_started(ctx.id()).await;
+                })
+            },
+            post_command: |ctx| {
+                Box::pin(async move { ctx.data().shutdown.command_finished(ctx.id()).await })
+            },
+            command_check: Some(|ctx| Box::pin(cmds::feature_check(ctx))),
+            on_error: |error| {
+                Box::pin(async move {
+                    if let Some(ctx) = error.ctx() {
+                        ctx.data().shutdown.command_finished(ctx.id()).await;
+                    }
+                    error::on_error(error).await
+                })
+            },
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
@@ -99,10 +334,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
         })
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                register_commands(ctx, &framework.options().commands, test_guild).await?;
+                settings.home(&ctx.http).await;
+                settings.spawn_twitch_sync(&data_shutdown, rpc.clone(), String::from("discord"));
                 Ok(Data {
-                    color,
-                    active_duels: Arc::new(Mutex::new(HashMap::new())),
+                    settings,
+                    duels,
+                    profiles,
+                    ratings,
+                    duel_results,
+                    wallets,
+                    bets,
+                    links,
+                    rolesync,
+                    rpc,
+                    chatters,
+                    redis: redis_con,
+                    stats,
+                    shutdown: data_shutdown,
                 })
             })
         })
@@ -112,24 +361,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
         | serenity::GatewayIntents::MESSAGE_CONTENT
         | serenity::GatewayIntents::GUILD_MESSAGES;
 
-    let discord_client = serenity::ClientBuilder::new(&discord_token, intents)
+    let mut discord_client = serenity::ClientBuilder::new(&discord_token, intents)
         .framework(framework)
-        .event_handler(Handler)
-        .await;
+        .event_handler(Handler {
+            stats: handler_stats.clone(),
+        })
+        .await?;
+
+    shutdown.spawn(rolesync::start_sweeper(
+        serenity::Http::new(&discord_token),
+        listener_rolesync.clone(),
+        shutdown.token(),
+    ));
 
-    let redis_url = std::env::var("REDIS_URL").unwrap_or("redis://redis:6379".into());
+    shutdown.spawn(duels::start_timeouts(
+        serenity::Http::new(&discord_token),
+        timeout_duels,
+        timeout_results,
+        shutdown.token(),
+    ));
 
     tokio::spawn(async move {
-        if let Err(e) =
-            events::twitch::start_redis_listener(serenity::Http::new(&discord_token), &redis_url)
-                .await
+        rpc::start_reply_listener(&reply_redis_url, reply_rpc).await;
+    });
+
+    shutdown.spawn(async move {
+        if let Err(e) = events::twitch::start_redis_listener(
+            serenity::Http::new(&discord_token),
+            listener_settings,
+            listener_links,
+            listener_rolesync,
+            listener_chatters,
+            listener_stats.clone(),
+            listener_shutdown,
+        )
+        .await
         {
-            eprintln!("Redis listener error: {e}");
+            tracing::error!(error = %e, "Twitch event listener stopped");
         }
+        listener_stats.set_listener_alive(false);
     });
 
-    if let Err(why) = discord_client.unwrap().start().await {
-        println!("Client error: {why:?}");
+    let shard_manager = discord_client.shard_manager.clone();
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        tracing::info!("Shutting down");
+        handler_stats.set_discord_ready(false);
+        signal_shutdown.begin();
+        shard_manager.shutdown_all().await;
+    });
+
+    if let Err(why) = discord_client.start().await {
+        tracing::error!(error = ?why, "Discord client error");
+    }
+
+    shutdown.begin();
+    if shutdown.drain(shutdown::DRAIN_DEADLINE).await {
+        tracing::info!("Finished in-flight work");
     }
     Ok(())
 }
@@ -0,0 +1,156 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{Router, extract::State, http::StatusCode, routing::get};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::{duels::Duels, stats::BridgeStats};
+
+/// Prometheus collectors of the bot.
+pub struct Metrics {
+    registry: Registry,
+    pub events_received: IntCounterVec,
+    pub events_relayed: IntCounterVec,
+    pub parse_failures: IntCounter,
+    pub discord_send_errors: IntCounter,
+    pub handler_failures: IntCounterVec,
+    pub relay_latency: HistogramVec,
+    pub active_duels: IntGauge,
+    pub command_invocations: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("synapse".into()), None)
+            .expect("Failed to create the metrics registry");
+
+        let events_received = IntCounterVec::new(
+            Opts::new("events_received_total", "Bridge events received, per type"),
+            &["event_type"],
+        )
+        .unwrap();
+        let events_relayed = IntCounterVec::new(
+            Opts::new(
+                "events_relayed_total",
+                "Bridge events relayed successfully, per type",
+            ),
+            &["event_type"],
+        )
+        .unwrap();
+        let parse_failures = IntCounter::new(
+            "parse_failures_total",
+            "Bridge payloads that could not be parsed",
+        )
+        .unwrap();
+        let discord_send_errors = IntCounter::new(
+            "discord_send_errors_total",
+            "Bridge events that failed to be posted on Discord",
+        )
+        .unwrap();
+        let handler_failures = IntCounterVec::new(
+            Opts::new(
+                "handler_failures_total",
+                "Bridge events whose handler failed, per type and error kind",
+            ),
+            &["event_type", "kind"],
+        )
+        .unwrap();
+        let relay_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "relay_latency_seconds",
+                "Time between receiving a bridge event and finishing its relay",
+            )
+            .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+            &["event_type"],
+        )
+        .unwrap();
+        let active_duels = IntGauge::new("active_duels", "Duels currently in progress").unwrap();
+        let command_invocations = IntCounterVec::new(
+            Opts::new("command_invocations_total", "Commands invoked, per command"),
+            &["command"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(events_received.clone()))
+            .unwrap();
+        registry.register(Box::new(events_relayed.clone())).unwrap();
+        registry.register(Box::new(parse_failures.clone())).unwrap();
+        registry
+            .register(Box::new(discord_send_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(handler_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(relay_latency.clone())).unwrap();
+        registry.register(Box::new(active_duels.clone())).unwrap();
+        registry
+            .register(Box::new(command_invocations.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            events_received,
+            events_relayed,
+            parse_failures,
+            discord_send_errors,
+            handler_failures,
+            relay_latency,
+            active_duels,
+            command_invocations,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+struct HttpState {
+    stats: Arc<BridgeStats>,
+    duels: Duels,
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<HttpState>) -> (StatusCode, &'static str) {
+    if state.stats.discord_ready() && state.stats.listener_alive() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+async fn metrics(State(state): State<HttpState>) -> String {
+    let metrics = state.stats.metrics();
+    metrics.active_duels.set(state.duels.count().await as i64);
+    metrics.encode()
+}
+
+/// Serves `/healthz`, `/readyz` and `/metrics` on `addr`.
+pub async fn serve(addr: SocketAddr, stats: Arc<BridgeStats>, duels: Duels) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .with_state(HttpState { stats, duels });
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(%addr, "Serving health checks and metrics");
+    axum::serve(listener, app).await
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use poise::serenity_prelude::UserId;
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+
+use crate::duels::engine::{BASE_HP, Stats};
+
+pub const MAX_LEVEL: u32 = 30;
+/// XP for winning a duel.
+pub const WIN_XP: u64 = 50;
+/// XP for losing a duel that was fought to the end.
+pub const LOSS_XP: u64 = 15;
+const HP_PER_LEVEL: u32 = 5;
+/// Levels per point of bonus damage.
+const LEVELS_PER_DAMAGE: u32 = 2;
+
+fn profile_key(user_id: UserId) -> String {
+    format!("profile:{user_id}")
+}
+
+/// Total XP needed to reach `level`.
+fn level_xp(level: u32) -> u64 {
+    let level = u64::from(level);
+    50 * level * (level - 1)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub wins: u64,
+    pub losses: u64,
+    pub xp: u64,
+}
+
+impl Profile {
+    pub fn level(&self) -> u32 {
+        (1..=MAX_LEVEL)
+            .rev()
+            .find(|level| self.xp >= level_xp(*level))
+            .unwrap_or(1)
+    }
+
+    /// Total XP needed for the next level, `None` at the maximum level.
+    pub fn next_level_xp(&self) -> Option<u64> {
+        let level = self.level();
+        (level < MAX_LEVEL).then(|| level_xp(level + 1))
+    }
+
+    /// What the player starts duels with at their level.
+    pub fn stats(&self) -> Stats {
+        let level = self.level();
+        Stats {
+            max_hp: BASE_HP + HP_PER_LEVEL * (level - 1),
+            bonus_damage: (level - 1) / LEVELS_PER_DAMAGE,
+        }
+    }
+}
+
+/// Someone who levelled up with a duel.
+pub struct LevelUp {
+    pub user_id: UserId,
+    pub level: u32,
+}
+
+/// Redis-backed duel records and experience of the players.
+#[derive(Clone)]
+pub struct Profiles {
+    con: ConnectionManager,
+}
+
+impl Profiles {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    pub async fn get(&self, user_id: UserId) -> RedisResult<Profile> {
+        let mut con = self.con.clone();
+        let fields: HashMap<String, u64> = con.hgetall(profile_key(user_id)).await?;
+        let field = |name: &str| fields.get(name).copied().unwrap_or_default();
+        Ok(Profile {
+            wins: field("wins"),
+            losses: field("losses"),
+            xp: field("xp"),
+        })
+    }
+
+    /// Records a finished duel, a loser who forfeited earns no XP.
+    pub async fn record_duel(
+        &self,
+        winner: UserId,
+        loser: UserId,
+        forfeit: bool,
+    ) -> RedisResult<Vec<LevelUp>> {
+        let before = [self.get(winner).await?, self.get(loser).await?];
+        let loss_xp = if forfeit { 0 } else { LOSS_XP };
+
+        let mut con = self.con.clone();
+        let (winner_xp, loser_xp): (u64, u64) = redis::pipe()
+            .atomic()
+            .hincr(profile_key(winner), "wins", 1)
+            .ignore()
+            .hincr(profile_key(winner), "xp", WIN_XP)
+            .hincr(profile_key(loser), "losses", 1)
+            .ignore()
+            .hincr(profile_key(loser), "xp", loss_xp)
+            .query_async(&mut con)
+            .await?;
+
+        Ok([(winner, winner_xp), (loser, loser_xp)]
+            .into_iter()
+            .zip(before)
+            .filter_map(|((user_id, xp), before)| {
+                let level = Profile { xp, ..before }.level();
+                (level > before.level()).then_some(LevelUp { user_id, level })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_xp(xp: u64) -> Profile {
+        Profile {
+            xp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn levels_start_at_one() {
+        assert_eq!(level_xp(1), 0);
+        assert_eq!(with_xp(0).level(), 1);
+        assert_eq!(with_xp(0).next_level_xp(), Some(100));
+    }
+
+    #[test]
+    fn levels_up_at_the_boundaries() {
+        assert_eq!(with_xp(99).level(), 1);
+        assert_eq!(with_xp(100).level(), 2);
+        assert_eq!(with_xp(299).level(), 2);
+        assert_eq!(with_xp(300).level(), 3);
+        assert_eq!(with_xp(300).next_level_xp(), Some(600));
+    }
+
+    #[test]
+    fn stops_at_the_maximum_level() {
+        let max = with_xp(level_xp(MAX_LEVEL));
+        assert_eq!(with_xp(level_xp(MAX_LEVEL) - 1).level(), MAX_LEVEL - 1);
+        assert_eq!(max.level(), MAX_LEVEL);
+        assert_eq!(max.next_level_xp(), None);
+        assert_eq!(with_xp(u64::MAX).level(), MAX_LEVEL);
+    }
+
+    #[test]
+    fn stats_scale_with_the_level() {
+        assert_eq!(
+            with_xp(0).stats(),
+            Stats {
+                max_hp: BASE_HP,
+                bonus_damage: 0,
+            }
+        );
+        assert_eq!(
+            with_xp(level_xp(2)).stats(),
+            Stats {
+                max_hp: BASE_HP + HP_PER_LEVEL,
+                bonus_damage: 0,
+            }
+        );
+        assert_eq!(
+            with_xp(level_xp(3)).stats(),
+            Stats {
+                max_hp: BASE_HP + 2 * HP_PER_LEVEL,
+                bonus_damage: 1,
+            }
+        );
+        assert_eq!(
+            with_xp(level_xp(MAX_LEVEL)).stats(),
+            Stats {
+                max_hp: BASE_HP + (MAX_LEVEL - 1) * HP_PER_LEVEL,
+                bonus_damage: (MAX_LEVEL - 1) / LEVELS_PER_DAMAGE,
+            }
+        );
+    }
+}
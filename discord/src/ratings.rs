@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use poise::serenity_prelude::{GuildId, UserId};
+use redis::{AsyncCommands, RedisResult, aio::ConnectionManager};
+
+/// Rating of a member who hasn't finished a duel this season.
+pub const DEFAULT_RATING: i64 = 1000;
+/// Most points a duel can move a rating by.
+const K_FACTOR: f64 = 32.0;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+fn season_key(guild_id: GuildId) -> String {
+    format!("rating:season:{guild_id}")
+}
+
+fn ratings_key(guild_id: GuildId, season: u64) -> String {
+    format!("rating:{guild_id}:{season}")
+}
+
+fn rollover_key(guild_id: GuildId, season: u64) -> String {
+    format!("rating:rollover:{guild_id}:{season}")
+}
+
+/// Points the winner takes from the loser, by the Elo formula.
+fn elo_delta(winner: i64, loser: i64) -> i64 {
+    let expected = 1.0 / (1.0 + 10f64.powf((loser - winner) as f64 / 400.0));
+    (K_FACTOR * (1.0 - expected)).round().max(1.0) as i64
+}
+
+/// Whether a season that started at `started` is over at `now`, never when `season_days` is 0.
+fn season_over(started: i64, now: i64, season_days: u64) -> bool {
+    season_days > 0 && now - started >= season_days as i64 * DAY_SECS
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Season {
+    pub number: u64,
+    /// Unix time the season started.
+    pub started: i64,
+}
+
+/// A rating after a duel.
+#[derive(Debug, Clone, Copy)]
+pub struct RatingChange {
+    pub user_id: UserId,
+    pub rating: i64,
+    pub delta: i64,
+}
+
+/// Redis-backed duel ratings of the members of each guild, by season.
+#[derive(Clone)]
+pub struct Ratings {
+    con: ConnectionManager,
+}
+
+impl Ratings {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    /// The season being played in `guild_id`, a new one starts once `season_days` passed.
+    ///
+    /// Seasons only end with `/leaderboard reset` when `season_days` is 0.
+    pub async fn season(&self, guild_id: GuildId, season_days: u64) -> RedisResult<Season> {
+        let mut con = self.con.clone();
+        let now = chrono::Utc::now().timestamp();
+        let _: () = redis::pipe()
+            .hset_nx(season_key(guild_id), "number", 1)
+            .ignore()
+            .hset_nx(season_key(guild_id), "started", now)
+            .ignore()
+            .query_async(&mut con)
+            .await?;
+
+        let fields: HashMap<String, i64> = con.hgetall(season_key(guild_id)).await?;
+        let season = Season {
+            number: fields.get("number").copied().unwrap_or(1) as u64,
+            started: fields.get("started").copied().unwrap_or(now),
+        };
+
+        if season_over(season.started, now, season_days) {
+            return self.next_season(guild_id, season).await;
+        }
+        Ok(season)
+    }
+
+    /// Ends `current` and starts the next season, ratings go back to the default.
+    ///
+    /// Only the first caller moves on when several end the same season at once.
+    pub async fn next_season(&self, guild_id: GuildId, current: Season) -> RedisResult<Season> {
+        let mut con = self.con.clone();
+        let now = chrono::Utc::now().timestamp();
+        let next = Season {
+            number: current.number + 1,
+            started: now,
+        };
+
+        let first: bool = redis::cmd("SET")
+            .arg(rollover_key(guild_id, current.number))
+            .arg(now)
+            .arg("NX")
+            .arg("EX")
+            .arg(DAY_SECS)
+            .query_async::<Option<String>>(&mut con)
+            .await?
+            .is_some();
+        if first {
+            let _: () = redis::pipe()
+                .atomic()
+                .hset(season_key(guild_id), "number", next.number)
+                .hset(season_key(guild_id), "started", next.started)
+                .query_async(&mut con)
+                .await?;
+            tracing::info!(guild = %guild_id, season = next.number, "Started a new duel season");
+            return Ok(next);
+        }
+
+        let fields: HashMap<String, i64> = con.hgetall(season_key(guild_id)).await?;
+        Ok(Season {
+            number: fields.get("number").copied().unwrap_or(1) as u64,
+            started: fields.get("started").copied().unwrap_or(now),
+        })
+    }
+
+    pub async fn rating(
+        &self,
+        guild_id: GuildId,
+        season: &Season,
+        user_id: UserId,
+    ) -> RedisResult<i64> {
+        let mut con = self.con.clone();
+        let rating: Option<i64> = con
+            .zscore(ratings_key(guild_id, season.number), user_id.get())
+            .await?;
+        Ok(rating.unwrap_or(DEFAULT_RATING))
+    }
+
+    /// Moves points from the loser to the winner and returns both new ratings.
+    pub async fn record_duel(
+        &self,
+        guild_id: GuildId,
+        season: &Season,
+        winner: UserId,
+        loser: UserId,
+    ) -> RedisResult<[RatingChange; 2]> {
+        let winner_rating = self.rating(guild_id, season, winner).await?;
+        let loser_rating = self.rating(guild_id, season, loser).await?;
+        let delta = elo_delta(winner_rating, loser_rating);
+
+        let key = ratings_key(guild_id, season.number);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        // Members start the season at the default rating.
+        for user_id in [winner, loser] {
+            pipe.cmd("ZADD")
+                .arg(&key)
+                .arg("NX")
+                .arg(DEFAULT_RATING)
+                .arg(user_id.get())
+                .ignore();
+        }
+        let (winner_rating, loser_rating): (f64, f64) = pipe
+            .zincr(&key, winner.get(), delta)
+            .zincr(&key, loser.get(), -delta)
+            .query_async(&mut self.con.clone())
+            .await?;
+
+        Ok([
+            RatingChange {
+                user_id: winner,
+                rating: winner_rating as i64,
+                delta,
+            },
+            RatingChange {
+                user_id: loser,
+                rating: loser_rating as i64,
+                delta: -delta,
+            },
+        ])
+    }
+
+    /// Members ranked by rating, best first.
+    pub async fn page(
+        &self,
+        guild_id: GuildId,
+        season: &Season,
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<(UserId, i64)>> {
+        let mut con = self.con.clone();
+        let entries: Vec<(u64, f64)> = con
+            .zrevrange_withscores(
+                ratings_key(guild_id, season.number),
+                offset as isize,
+                (offset + count) as isize - 1,
+            )
+            .await?;
+        Ok(entries
+            .into_iter()
+            .map(|(user_id, rating)| (UserId::new(user_id), rating as i64))
+            .collect())
+    }
+
+    /// Members with a rating this season.
+    pub async fn ranked(&self, guild_id: GuildId, season: &Season) -> RedisResult<usize> {
+        let mut con = self.con.clone();
+        con.zcard(ratings_key(guild_id, season.number)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_ratings_move_half_the_k_factor() {
+        assert_eq!(elo_delta(1000, 1000), 16);
+        assert_eq!(elo_delta(1500, 1500), 16);
+    }
+
+    #[test]
+    fn upsets_move_more_points() {
+        assert_eq!(elo_delta(1000, 1400), 29);
+        assert_eq!(elo_delta(1400, 1000), 3);
+    }
+
+    #[test]
+    fn both_outcomes_add_up_to_the_k_factor() {
+        for (a, b) in [(1000, 1000), (1000, 1100), (1200, 1000), (900, 1600)] {
+            assert_eq!(
+                elo_delta(a, b) + elo_delta(b, a),
+                K_FACTOR as i64,
+                "{a} vs {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_win_is_always_worth_a_point() {
+        assert_eq!(elo_delta(3000, 1000), 1);
+    }
+
+    #[test]
+    fn seasons_end_after_their_length() {
+        let started = 1_000_000;
+        assert!(!season_over(started, started + 7 * DAY_SECS - 1, 7));
+        assert!(season_over(started, started + 7 * DAY_SECS, 7));
+        assert!(season_over(started, started + 30 * DAY_SECS, 7));
+    }
+
+    #[test]
+    fn seasons_without_a_length_never_end() {
+        assert!(!season_over(0, 365 * DAY_SECS, 0));
+    }
+}
@@ -0,0 +1,315 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{self as serenity, GuildId, HttpError, RoleId, StatusCode, UserId};
+use redis::{AsyncCommands, RedisResult, Script, aio::ConnectionManager};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+
+use crate::{Error, config::SharedConfig, links::Links};
+
+const SUB_PERIOD_SECS: i64 = 30 * 24 * 60 * 60;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+const AUDIT_REASON: &str = "Twitch role sync";
+
+/// Stops tracking `ARGV[1]` in `KEYS[1]` if it still expires by `ARGV[2]`, returns 1 if it did.
+const UNTRACK_SCRIPT: &str = r"
+local until = redis.call('ZSCORE', KEYS[1], ARGV[1])
+if until and tonumber(until) <= tonumber(ARGV[2]) then
+    return redis.call('ZREM', KEYS[1], ARGV[1])
+end
+return 0
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    Subscriber,
+    Vip,
+    Moderator,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Subscriber, Tier::Vip, Tier::Moderator];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tier::Subscriber => "Subscriber",
+            Tier::Vip => "VIP",
+            Tier::Moderator => "Moderator",
+        }
+    }
+
+    fn badges(&self) -> &'static [&'static str] {
+        match self {
+            Tier::Subscriber => &["subscriber", "founder"],
+            Tier::Vip => &["vip"],
+            Tier::Moderator => &["moderator"],
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Tier::Subscriber => "rolesync:expiry:subscriber",
+            Tier::Vip => "rolesync:expiry:vip",
+            Tier::Moderator => "rolesync:expiry:moderator",
+        }
+    }
+}
+
+fn default_grace_period_hours() -> i64 {
+    72
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoleSyncConfig {
+    pub guild_id: Option<u64>,
+    pub subscriber_role: Option<u64>,
+    pub vip_role: Option<u64>,
+    pub moderator_role: Option<u64>,
+    #[serde(default = "default_grace_period_hours")]
+    pub grace_period_hours: i64,
+}
+
+impl Default for RoleSyncConfig {
+    fn default() -> Self {
+        Self {
+            guild_id: None,
+            subscriber_role: None,
+            vip_role: None,
+            moderator_role: None,
+            grace_period_hours: default_grace_period_hours(),
+        }
+    }
+}
+
+/// Grants and removes Discord roles for linked users based on their Twitch status.
+///
+/// Every tracked member has an expiry timestamp per tier, stored in a Redis sorted set.
+/// Seeing the badge or a subscription event pushes it forward, seeing a linked user
+/// chat without the badge pulls it back to the grace period, and the sweeper removes
+/// the role once it is in the past.
+#[derive(Clone)]
+pub struct RoleSync {
+    con: ConnectionManager,
+    links: Links,
+    config: SharedConfig,
+}
+
+impl RoleSync {
+    pub fn new(con: ConnectionManager, links: Links, config: SharedConfig) -> Self {
+        Self { con, links, config }
+    }
+
+    pub fn guild(&self) -> Option<GuildId> {
+        self.config
+            .get()
+            .role_sync
+            .guild_id
+            .filter(|id| *id != 0)
+            .map(GuildId::new)
+    }
+
+    pub fn role(&self, tier: Tier) -> Option<RoleId> {
+        let config = &self.config.get().role_sync;
+        match tier {
+            Tier::Subscriber => config.subscriber_role,
+            Tier::Vip => config.vip_role,
+            Tier::Moderator => config.moderator_role,
+        }
+        .filter(|id| *id != 0)
+        .map(RoleId::new)
+    }
+
+    fn grace_secs(&self) -> i64 {
+        self.config.get().role_sync.grace_period_hours * 60 * 60
+    }
+
+    /// Refreshes every tier of a linked chatter from the badges of one of their messages.
+    pub async fn observe_badges(
+        &self,
+        http_client: &serenity::Http,
+        twitch_id: &str,
+        badges: &Value,
+    ) -> Result<(), Error> {
+        let Some(discord_id) = self.linked_user(Some(twitch_id), None).await? else {
+            return Ok(());
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        for tier in Tier::ALL {
+            let has_badge = tier.badges().iter().any(|b| !badges[*b].is_null());
+            if has_badge {
+                self.extend(http_client, discord_id, tier, now + self.grace_secs())
+                    .await?;
+            } else {
+                self.shorten(discord_id, tier, now + self.grace_secs())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks a linked user as subscribed for another month, from a subscription, resub or gift.
+    pub async fn observe_subscription(
+        &self,
+        http_client: &serenity::Http,
+        twitch_id: Option<&str>,
+        login: &str,
+    ) -> Result<(), Error> {
+        let Some(discord_id) = self.linked_user(twitch_id, Some(login)).await? else {
+            return Ok(());
+        };
+
+        let until = chrono::Utc::now().timestamp() + SUB_PERIOD_SECS + self.grace_secs();
+        self.extend(http_client, discord_id, Tier::Subscriber, until)
+            .await
+    }
+
+    /// Starts the grace period of every tier, e.g. after the user unlinked their account.
+    pub async fn forget(&self, discord_id: UserId) -> Result<(), Error> {
+        let now = chrono::Utc::now().timestamp();
+        for tier in Tier::ALL {
+            self.shorten(discord_id, tier, now).await?;
+        }
+        Ok(())
+    }
+
+    /// Lists tracked members of a tier with the timestamp their role expires at.
+    pub async fn tracked(&self, tier: Tier) -> RedisResult<Vec<(UserId, i64)>> {
+        let mut con = self.con.clone();
+
+        let entries: Vec<(u64, i64)> = con.zrange_withscores(tier.key(), 0, -1).await?;
+        Ok(entries
+            .into_iter()
+            .filter(|(id, _)| *id != 0)
+            .map(|(id, until)| (UserId::new(id), until))
+            .collect())
+    }
+
+    /// Removes the roles of every member whose expiry is in the past.
+    pub async fn sweep(&self, http_client: &serenity::Http) -> Result<usize, Error> {
+        let mut con = self.con.clone();
+        let now = chrono::Utc::now().timestamp();
+        let mut removed = 0;
+
+        for tier in Tier::ALL {
+            let expired: Vec<u64> = con.zrangebyscore(tier.key(), "-inf", now).await?;
+
+            for id in expired.into_iter().filter(|id| *id != 0) {
+                let removal = match (self.guild(), self.role(tier)) {
+                    (Some(guild), Some(role)) => {
+                        http_client
+                            .remove_member_role(guild, UserId::new(id), role, Some(AUDIT_REASON))
+                            .await
+                    }
+                    _ => Ok(()),
+                };
+                match removal {
+                    Ok(()) => {}
+                    // The member left or the role was deleted, there is nothing left to remove.
+                    Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                        if response.status_code == StatusCode::NOT_FOUND => {}
+                    // Still tracked, the next sweep tries again.
+                    Err(e) => {
+                        tracing::warn!(user = id, tier = tier.name(), error = %e, "Failed to remove synced role");
+                        continue;
+                    }
+                }
+
+                // The expiry may have been extended since it was read.
+                let untracked: usize = Script::new(UNTRACK_SCRIPT)
+                    .key(tier.key())
+                    .arg(id)
+                    .arg(now)
+                    .invoke_async(&mut con)
+                    .await?;
+                removed += untracked;
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn linked_user(
+        &self,
+        twitch_id: Option<&str>,
+        login: Option<&str>,
+    ) -> Result<Option<UserId>, Error> {
+        if self.guild().is_none() {
+            return Ok(None);
+        }
+
+        let by_id = match twitch_id.filter(|id| !id.is_empty()) {
+            Some(id) => self.links.discord_user(id).await?,
+            None => None,
+        };
+        match (by_id, login) {
+            (Some(id), _) => Ok(Some(id)),
+            (None, Some(login)) => Ok(self.links.discord_user_by_login(login).await?),
+            (None, None) => Ok(None),
+        }
+    }
+
+    async fn extend(
+        &self,
+        http_client: &serenity::Http,
+        discord_id: UserId,
+        tier: Tier,
+        until: i64,
+    ) -> Result<(), Error> {
+        let (Some(guild), Some(role)) = (self.guild(), self.role(tier)) else {
+            return Ok(());
+        };
+        let mut con = self.con.clone();
+
+        let changed: usize = redis::cmd("ZADD")
+            .arg(tier.key())
+            .arg("GT")
+            .arg("CH")
+            .arg(until)
+            .arg(discord_id.get())
+            .query_async(&mut con)
+            .await?;
+
+        // Granted whenever the expiry moves, so a grant that failed is tried again on the
+        // next extension while the member stays tracked for the sweeper.
+        if changed > 0 {
+            http_client
+                .add_member_role(guild, discord_id, role, Some(AUDIT_REASON))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn shorten(&self, discord_id: UserId, tier: Tier, until: i64) -> Result<(), Error> {
+        let mut con = self.con.clone();
+
+        let _: usize = redis::cmd("ZADD")
+            .arg(tier.key())
+            .arg("XX")
+            .arg("LT")
+            .arg(until)
+            .arg(discord_id.get())
+            .query_async(&mut con)
+            .await?;
+        Ok(())
+    }
+}
+
+pub async fn start_sweeper(
+    http_client: serenity::Http,
+    rolesync: RoleSync,
+    token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = token.cancelled() => return,
+        }
+        match rolesync.sweep(&http_client).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!(removed = n, "Removed expired synced roles"),
+            Err(e) => tracing::error!(error = %e, "Role sync sweep failed"),
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use poise::serenity_prelude::futures::StreamExt;
+use redis::{AsyncCommands, RedisError, RedisResult, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, oneshot};
+
+/// Channel the Twitch service listens on for requests.
+pub const REQUEST_CHANNEL: &str = "twitch_rpc";
+
+const REPLY_CHANNEL_PREFIX: &str = "twitch_rpc_reply";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Waits before subscribing again to the replies, doubled after each failed attempt.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "action",
+    rename_all = "lowercase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ModerationCommand {
+    Timeout {
+        username: String,
+        duration: u32,
+        reason: Option<String>,
+    },
+    Ban {
+        username: String,
+        reason: Option<String>,
+    },
+    Unban {
+        username: String,
+    },
+    Delete {
+        message_id: String,
+    },
+    Clear,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "action",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ChatSetting {
+    Slow { enabled: bool, seconds: u32 },
+    EmoteOnly { enabled: bool },
+    FollowersOnly { enabled: bool, minutes: u32 },
+    SubsOnly { enabled: bool },
+    UniqueChat { enabled: bool },
+}
+
+/// Chat restrictions of the Twitch channel, as reported by the Twitch service.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RoomState {
+    /// Seconds between messages, `0` when slow mode is off.
+    pub slow: u32,
+    pub emote_only: bool,
+    /// Minimum follow age in minutes, `-1` when followers-only mode is off.
+    pub followers_only: i64,
+    pub subs_only: bool,
+    pub unique_chat: bool,
+}
+
+/// Connection state of the Twitch service.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TwitchStatus {
+    /// tmi.js ready state: `CONNECTING`, `OPEN`, `CLOSING` or `CLOSED`.
+    pub state: String,
+    pub channels: Vec<String>,
+    pub uptime_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "params", rename_all = "camelCase")]
+pub enum Request {
+    Ping,
+    Status,
+    Moderation(ModerationCommand),
+    ChatSettings(ChatSetting),
+    Join { channel: String },
+    Part { channel: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "type",
+    content = "params",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Response {
+    Pong { latency_ms: u64 },
+    Status(TwitchStatus),
+    Done,
+    RoomState(RoomState),
+}
+
+#[derive(Debug)]
+pub enum RpcError {
+    /// Nobody is subscribed to the request channel, or our reply channel isn't being listened to.
+    Unavailable,
+    Timeout,
+    /// The Twitch service handled the request and reported a failure.
+    Remote(String),
+    UnexpectedResponse,
+    Redis(RedisError),
+    Encoding(serde_json::Error),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Unavailable => write!(f, "The Twitch service is not reachable."),
+            RpcError::Timeout => write!(f, "The Twitch service did not answer in time."),
+            RpcError::Remote(e) => write!(f, "{e}"),
+            RpcError::UnexpectedResponse => {
+                write!(f, "The Twitch service sent an unexpected response.")
+            }
+            RpcError::Redis(e) => write!(f, "Redis error: {e}"),
+            RpcError::Encoding(e) => write!(f, "Encoding error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<RedisError> for RpcError {
+    fn from(e: RedisError) -> Self {
+        RpcError::Redis(e)
+    }
+}
+
+impl From<serde_json::Error> for RpcError {
+    fn from(e: serde_json::Error) -> Self {
+        RpcError::Encoding(e)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestEnvelope<'a> {
+    id: &'a str,
+    reply_to: &'a str,
+    issued_by: &'a str,
+    /// Twitch channel the request applies to, the service's own channel if `None`.
+    channel: Option<&'a str>,
+    request: &'a Request,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyEnvelope {
+    id: String,
+    ok: bool,
+    error: Option<String>,
+    response: Option<Response>,
+}
+
+/// Request/response calls to the Twitch service over Redis pub/sub.
+///
+/// Every request carries a correlation id and the reply channel of this instance;
+/// the reply listener routes answers back to the caller waiting on that id.
+#[derive(Clone)]
+pub struct RpcClient {
+    con: ConnectionManager,
+    reply_channel: Arc<str>,
+    listening: Arc<AtomicBool>,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<ReplyEnvelope>>>>,
+}
+
+impl RpcClient {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self {
+            con,
+            reply_channel: format!("{REPLY_CHANNEL_PREFIX}:{}", uuid::Uuid::new_v4()).into(),
+            listening: Arc::new(AtomicBool::new(false)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn call(&self, request: Request, issued_by: &str) -> Result<Response, RpcError> {
+        self.call_with_timeout(request, None, issued_by, DEFAULT_TIMEOUT)
+            .await
+    }
+
+    /// Like `call`, for a request about the Twitch channel `channel`.
+    pub async fn call_in(
+        &self,
+        request: Request,
+        channel: Option<&str>,
+        issued_by: &str,
+    ) -> Result<Response, RpcError> {
+        self.call_with_timeout(request, channel, issued_by, DEFAULT_TIMEOUT)
+            .await
+    }
+
+    pub async fn call_with_timeout(
+        &self,
+        request: Request,
+        channel: Option<&str>,
+        issued_by: &str,
+        timeout: Duration,
+    ) -> Result<Response, RpcError> {
+        if !self.listening.load(Ordering::Relaxed) {
+            return Err(RpcError::Unavailable);
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let payload = serde_json::to_string(&RequestEnvelope {
+            id: &id,
+            reply_to: &self.reply_channel,
+            issued_by,
+            channel,
+            request: &request,
+        })?;
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id.clone(), tx);
+
+        let mut con = self.con.clone();
+        let receivers: RedisResult<usize> = con.publish(REQUEST_CHANNEL, payload).await;
+        let receivers = match receivers {
+            Ok(receivers) => receivers,
+            Err(e) => {
+                self.pending.lock().await.remove(&id);
+                return Err(e.into());
+            }
+        };
+        if receivers == 0 {
+            self.pending.lock().await.remove(&id);
+            return Err(RpcError::Unavailable);
+        }
+
+        let reply = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return Err(RpcError::Unavailable),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                return Err(RpcError::Timeout);
+            }
+        };
+
+        match (reply.ok, reply.response) {
+            (true, Some(response)) => Ok(response),
+            (true, None) => Ok(Response::Done),
+            (false, _) => Err(RpcError::Remote(
+                reply
+                    .error
+                    .unwrap_or_else(|| String::from("Unknown Twitch error")),
+            )),
+        }
+    }
+
+    /// Round-trip latency between the Twitch service and Twitch's servers.
+    pub async fn ping(&self, issued_by: &str) -> Result<u64, RpcError> {
+        match self.call(Request::Ping, issued_by).await? {
+            Response::Pong { latency_ms } => Ok(latency_ms),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    pub async fn twitch_status(&self, issued_by: &str) -> Result<TwitchStatus, RpcError> {
+        match self.call(Request::Status, issued_by).await? {
+            Response::Status(status) => Ok(status),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    /// Number of calls still waiting for their reply.
+    pub async fn pending_count(&self) -> usize {
+        self.pending.lock().await.len()
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    pub async fn moderate(
+        &self,
+        command: ModerationCommand,
+        channel: Option<&str>,
+        issued_by: &str,
+    ) -> Result<(), RpcError> {
+        match self
+            .call_in(Request::Moderation(command), channel, issued_by)
+            .await?
+        {
+            Response::Done => Ok(()),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    /// Changes a chat restriction and returns the resulting room state.
+    pub async fn change_chat_setting(
+        &self,
+        setting: ChatSetting,
+        channel: Option<&str>,
+        issued_by: &str,
+    ) -> Result<RoomState, RpcError> {
+        match self
+            .call_in(Request::ChatSettings(setting), channel, issued_by)
+            .await?
+        {
+            Response::RoomState(state) => Ok(state),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    /// Makes the Twitch service listen to another channel.
+    pub async fn join(&self, channel: &str, issued_by: &str) -> Result<(), RpcError> {
+        let request = Request::Join {
+            channel: channel.into(),
+        };
+        match self.call(request, issued_by).await? {
+            Response::Done => Ok(()),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    pub async fn part(&self, channel: &str, issued_by: &str) -> Result<(), RpcError> {
+        let request = Request::Part {
+            channel: channel.into(),
+        };
+        match self.call(request, issued_by).await? {
+            Response::Done => Ok(()),
+            _ => Err(RpcError::UnexpectedResponse),
+        }
+    }
+
+    async fn resolve(&self, reply: ReplyEnvelope) {
+        if let Some(tx) = self.pending.lock().await.remove(&reply.id) {
+            let _ = tx.send(reply);
+        }
+    }
+
+    /// Fails every call still waiting for an answer, e.g. when the reply listener stops.
+    async fn abandon_pending(&self) {
+        self.listening.store(false, Ordering::Relaxed);
+        self.pending.lock().await.clear();
+    }
+}
+
+/// Listens for replies until the process exits, subscribing again whenever the connection drops.
+pub async fn start_reply_listener(redis_url: &str, rpc: RpcClient) {
+    let mut delay = RESUBSCRIBE_DELAY;
+    loop {
+        let result = listen_for_replies(redis_url, &rpc).await;
+        // The delay only grows while subscribing keeps failing.
+        if rpc.listening.load(Ordering::Relaxed) {
+            delay = RESUBSCRIBE_DELAY;
+        }
+        // Calls made before the drop won't get their reply.
+        rpc.abandon_pending().await;
+        match result {
+            Ok(()) => tracing::warn!(retry_in = ?delay, "RPC reply listener disconnected"),
+            Err(e) => tracing::error!(error = %e, retry_in = ?delay, "RPC reply listener failed"),
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+    }
+}
+
+async fn listen_for_replies(redis_url: &str, rpc: &RpcClient) -> RedisResult<()> {
+    let client = redis::Client::open(redis_url)?;
+    let mut conn = client.get_async_pubsub().await?;
+
+    conn.subscribe(rpc.reply_channel.as_ref()).await?;
+    let mut pubsub_stream = conn.on_message();
+    rpc.listening.store(true, Ordering::Relaxed);
+
+    tracing::info!(channel = %rpc.reply_channel, "Listening for RPC replies");
+
+    while let Some(msg) = pubsub_stream.next().await {
+        let payload: String = match msg.get_payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to read RPC reply");
+                continue;
+            }
+        };
+
+        match serde_json::from_str::<ReplyEnvelope>(&payload) {
+            Ok(reply) => rpc.resolve(reply).await,
+            Err(e) => tracing::warn!(error = %e, payload, "Failed to parse RPC reply"),
+        }
+    }
+
+    Ok(())
+}
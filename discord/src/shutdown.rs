@@ -0,0 +1,106 @@
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+use tokio_util::{
+    sync::CancellationToken,
+    task::{TaskTracker, task_tracker::TaskTrackerToken},
+};
+
+/// Time given to in-flight work once the bot stops taking new work.
+///
+/// Kept under the 10 seconds Docker waits after SIGTERM before killing the container.
+pub const DRAIN_DEADLINE: Duration = Duration::from_secs(8);
+
+/// Coordinates the shutdown of the bot.
+///
+/// Background workers stop when `token` is cancelled, and everything the bot is in the
+/// middle of (relays, commands, button clicks) is tracked so it can finish before exiting.
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+    commands: Arc<Mutex<HashMap<u64, TaskTrackerToken>>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            tracker: TaskTracker::new(),
+            commands: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Cancelled when the bot starts shutting down.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Spawns a background worker the shutdown waits for.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    /// Keeps the shutdown waiting until the returned token is dropped.
+    pub fn track(&self) -> TaskTrackerToken {
+        self.tracker.token()
+    }
+
+    /// Tracks a command from `pre_command` until it finishes or fails.
+    pub async fn command_started(&self, invocation_id: u64) {
+        self.commands
+            .lock()
+            .await
+            .insert(invocation_id, self.tracker.token());
+    }
+
+    pub async fn command_finished(&self, invocation_id: u64) {
+        self.commands.lock().await.remove(&invocation_id);
+    }
+
+    /// Stops the background workers.
+    pub fn begin(&self) {
+        self.token.cancel();
+    }
+
+    /// Waits for tracked work to finish, returns `false` if the deadline passed first.
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        self.tracker.close();
+        match tokio::time::timeout(deadline, self.tracker.wait()).await {
+            Ok(()) => true,
+            Err(_) => {
+                tracing::warn!(
+                    remaining = self.tracker.len(),
+                    "Shutdown deadline passed with work still in flight"
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Resolves on SIGTERM (`docker compose down`) or Ctrl+C.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+                    _ = tokio::signal::ctrl_c() => tracing::info!("Received Ctrl+C"),
+                }
+                return;
+            }
+            Err(e) => tracing::warn!(error = %e, "Can't listen for SIGTERM"),
+        }
+    }
+
+    if tokio::signal::ctrl_c().await.is_ok() {
+        tracing::info!("Received Ctrl+C");
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::Mutex;
+
+use crate::{Error, config::EVENTS, metrics::Metrics};
+
+/// Label of an event type in the metrics, types the bot doesn't know share one label.
+fn event_label(event_type: &str) -> &str {
+    if EVENTS.contains(&event_type) {
+        event_type
+    } else {
+        "unknown"
+    }
+}
+
+/// Health counters of the Twitch ↔ Discord bridge, shared between the listener and commands.
+pub struct BridgeStats {
+    started: Instant,
+    discord_ready: AtomicBool,
+    listener_alive: AtomicBool,
+    relayed_to_discord: AtomicU64,
+    relayed_to_twitch: AtomicU64,
+    failed: AtomicU64,
+    in_flight: AtomicU64,
+    last_events: Mutex<HashMap<String, DateTime<Utc>>>,
+    metrics: Metrics,
+}
+
+impl Default for BridgeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BridgeStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            discord_ready: AtomicBool::new(false),
+            listener_alive: AtomicBool::new(false),
+            relayed_to_discord: AtomicU64::new(0),
+            relayed_to_twitch: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            last_events: Mutex::new(HashMap::new()),
+            metrics: Metrics::new(),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn set_discord_ready(&self, ready: bool) {
+        self.discord_ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn discord_ready(&self) -> bool {
+        self.discord_ready.load(Ordering::Relaxed)
+    }
+
+    pub fn set_listener_alive(&self, alive: bool) {
+        self.listener_alive.store(alive, Ordering::Relaxed);
+    }
+
+    pub fn listener_alive(&self) -> bool {
+        self.listener_alive.load(Ordering::Relaxed)
+    }
+
+    /// Marks an event as received and being relayed, until `finish_event` is called.
+    pub async fn start_event(&self, event_type: &str) -> Instant {
+        let event_type = event_label(event_type);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .events_received
+            .with_label_values(&[event_type])
+            .inc();
+        self.last_events
+            .lock()
+            .await
+            .insert(event_type.to_string(), Utc::now());
+        Instant::now()
+    }
+
+    pub fn finish_event(&self, event_type: &str, started: Instant, result: &Result<(), Error>) {
+        let event_type = event_label(event_type);
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.metrics
+            .relay_latency
+            .with_label_values(&[event_type])
+            .observe(started.elapsed().as_secs_f64());
+        match result {
+            Ok(()) => {
+                self.relayed_to_discord.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .events_relayed
+                    .with_label_values(&[event_type])
+                    .inc();
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .handler_failures
+                    .with_label_values(&[event_type, e.kind()])
+                    .inc();
+                if let Error::Discord(_) = e {
+                    self.metrics.discord_send_errors.inc();
+                }
+            }
+        }
+    }
+
+    pub fn parse_failed(&self) {
+        self.metrics.parse_failures.inc();
+    }
+
+    pub fn relayed_to_twitch(&self) {
+        self.relayed_to_twitch.fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .events_relayed
+            .with_label_values(&["discord_message"])
+            .inc();
+    }
+
+    pub fn counts(&self) -> (u64, u64) {
+        (
+            self.relayed_to_discord.load(Ordering::Relaxed),
+            self.relayed_to_twitch.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Number of events whose handler failed.
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Last time each event type was received, most recent first.
+    pub async fn last_events(&self) -> Vec<(String, DateTime<Utc>)> {
+        let mut events: Vec<_> = self
+            .last_events
+            .lock()
+            .await
+            .iter()
+            .map(|(t, at)| (t.clone(), *at))
+            .collect();
+        events.sort_by_key(|(_, at)| std::cmp::Reverse(*at));
+        events
+    }
+}
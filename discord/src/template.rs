@@ -0,0 +1,142 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+/// A message with `{placeholder}`s, checked once against the placeholders it may use.
+///
+/// `{{` and `}}` render as literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn compile(source: &str, fields: &[&str]) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed `{{{name}`")),
+                        }
+                    }
+                    if !fields.contains(&name.as_str()) {
+                        return Err(format!(
+                            "unknown placeholder `{{{name}}}`, expected one of {}",
+                            fields
+                                .iter()
+                                .map(|f| format!("`{{{f}}}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Field(name));
+                }
+                '}' => return Err(String::from("unmatched `}`, use `}}` for a literal brace")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(Self {
+            source: source.into(),
+            segments,
+        })
+    }
+
+    /// Fills in the placeholders, missing values render as nothing.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Field(name) => {
+                    if let Some((_, value)) = values.iter().find(|(field, _)| field == name) {
+                        out.push_str(value);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &[&str] = &["user", "bits"];
+
+    #[test]
+    fn renders_placeholders_and_literal_braces() {
+        let template = Template::compile("{{{user}}} cheered {bits} bits", FIELDS).unwrap();
+        assert_eq!(
+            template.render(&[("user", "ferris"), ("bits", "100")]),
+            "{ferris} cheered 100 bits"
+        );
+        assert_eq!(template.to_string(), "{{{user}}} cheered {bits} bits");
+    }
+
+    #[test]
+    fn missing_values_render_as_nothing() {
+        let template = Template::compile("{user}: {bits}", FIELDS).unwrap();
+        assert_eq!(template.render(&[("bits", "1")]), ": 1");
+    }
+
+    #[test]
+    fn rejects_unclosed_placeholders() {
+        assert_eq!(
+            Template::compile("hi {user", FIELDS),
+            Err(String::from("unclosed `{user`"))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert_eq!(
+            Template::compile("{viewers} raiders", FIELDS),
+            Err(String::from(
+                "unknown placeholder `{viewers}`, expected one of `{user}`, `{bits}`"
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_unmatched_closing_braces() {
+        assert_eq!(
+            Template::compile("{user} }", FIELDS),
+            Err(String::from("unmatched `}`, use `}}` for a literal brace"))
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use poise::serenity_prelude::{GuildId, MessageId, UserId};
+use redis::{AsyncCommands, RedisResult, Script, aio::ConnectionManager};
+use serde::Deserialize;
+
+/// Name of the currency in messages.
+pub const CURRENCY: &str = "🪙";
+const DAILY_COOLDOWN_SECS: u64 = 24 * 60 * 60;
+/// Set once the wallets that predate the starting grant were marked as granted.
+const GRANTS_SEEDED_KEY: &str = "wallet:granted:seeded";
+
+/// Takes `ARGV[2]` from every user in `ARGV[3..]` and holds it under `ARGV[1]`.
+///
+/// Returns 0 once held, -1 if it already was, or the position of the first user who
+/// can't pay, counting from 1.
+const HOLD_SCRIPT: &str = r"
+if redis.call('HEXISTS', KEYS[2], ARGV[1]) == 1 then
+    return -1
+end
+local amount = tonumber(ARGV[2])
+for i = 3, #ARGV do
+    if tonumber(redis.call('HGET', KEYS[1], ARGV[i]) or '0') < amount then
+        return i - 2
+    end
+end
+for i = 3, #ARGV do
+    redis.call('HINCRBY', KEYS[1], ARGV[i], -amount)
+end
+redis.call('HSET', KEYS[2], ARGV[1], amount)
+return 0
+";
+
+/// Pays the amount held under `ARGV[1]`, times the multiplier following each user in `ARGV`.
+///
+/// Returns the amount that was held, 0 if nothing was.
+const RELEASE_SCRIPT: &str = r"
+local amount = tonumber(redis.call('HGET', KEYS[2], ARGV[1]) or '0')
+if amount == 0 then
+    return 0
+end
+redis.call('HDEL', KEYS[2], ARGV[1])
+for i = 2, #ARGV, 2 do
+    redis.call('HINCRBY', KEYS[1], ARGV[i], amount * tonumber(ARGV[i + 1]))
+end
+return amount
+";
+
+/// Gives `ARGV[2]` to `ARGV[1]` the first time their wallet is opened, returns the balance.
+const OPEN_SCRIPT: &str = r"
+if redis.call('SADD', KEYS[2], ARGV[1]) == 1 then
+    return redis.call('HINCRBY', KEYS[1], ARGV[1], ARGV[2])
+end
+return tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0')
+";
+
+/// Moves `ARGV[3]` from `ARGV[1]` to `ARGV[2]`, returns 0 if the sender can't pay.
+const TRANSFER_SCRIPT: &str = r"
+local amount = tonumber(ARGV[3])
+if tonumber(redis.call('HGET', KEYS[1], ARGV[1]) or '0') < amount then
+    return 0
+end
+redis.call('HINCRBY', KEYS[1], ARGV[1], -amount)
+redis.call('HINCRBY', KEYS[1], ARGV[2], amount)
+return 1
+";
+
+fn default_daily_amount() -> u64 {
+    100
+}
+
+fn default_starting_grant() -> u64 {
+    500
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EconomyConfig {
+    /// Coins members claim with `/daily`.
+    #[serde(default = "default_daily_amount")]
+    pub daily_amount: u64,
+    /// Coins members get the first time they use their wallet.
+    #[serde(default = "default_starting_grant")]
+    pub starting_grant: u64,
+}
+
+impl Default for EconomyConfig {
+    fn default() -> Self {
+        Self {
+            daily_amount: default_daily_amount(),
+            starting_grant: default_starting_grant(),
+        }
+    }
+}
+
+pub fn wallet_key(guild_id: GuildId) -> String {
+    format!("wallet:{guild_id}")
+}
+
+fn granted_key(guild_id: GuildId) -> String {
+    format!("wallet:granted:{guild_id}")
+}
+
+fn escrow_key(guild_id: GuildId) -> String {
+    format!("wallet:escrow:{guild_id}")
+}
+
+fn daily_key(guild_id: GuildId, user_id: UserId) -> String {
+    format!("wallet:daily:{guild_id}:{user_id}")
+}
+
+/// Why coins couldn't be held.
+pub enum HoldError {
+    /// This user doesn't have enough coins.
+    Insufficient(UserId),
+    AlreadyHeld,
+}
+
+/// Redis-backed coin balances of the members of each guild.
+#[derive(Clone)]
+pub struct Wallets {
+    con: ConnectionManager,
+}
+
+impl Wallets {
+    pub fn new(con: ConnectionManager) -> Self {
+        Self { con }
+    }
+
+    pub async fn balance(&self, guild_id: GuildId, user_id: UserId) -> RedisResult<u64> {
+        let mut con = self.con.clone();
+        let balance: Option<u64> = con.hget(wallet_key(guild_id), user_id.get()).await?;
+        Ok(balance.unwrap_or_default())
+    }
+
+    /// Like [`Wallets::balance`], but grants `starting_grant` to members new to the economy.
+    pub async fn open(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        starting_grant: u64,
+    ) -> RedisResult<u64> {
+        Script::new(OPEN_SCRIPT)
+            .key(wallet_key(guild_id))
+            .key(granted_key(guild_id))
+            .arg(user_id.get())
+            .arg(starting_grant)
+            .invoke_async(&mut self.con.clone())
+            .await
+    }
+
+    /// Marks every member who already has a wallet as granted, so the starting grant only
+    /// goes to members new to the economy. Only runs once, returns the wallets marked.
+    pub async fn seed_grants(&self) -> RedisResult<usize> {
+        let mut con = self.con.clone();
+        let seeded: bool = con.exists(GRANTS_SEEDED_KEY).await?;
+        if seeded {
+            return Ok(0);
+        }
+
+        let mut guilds = vec![];
+        let mut cursor = 0;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg("wallet:*")
+                .query_async(&mut con)
+                .await?;
+            guilds.extend(
+                keys.iter()
+                    .filter_map(|key| key.strip_prefix("wallet:")?.parse().ok())
+                    .filter(|&id| id != 0)
+                    .map(GuildId::new),
+            );
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        // SCAN may return a key more than once.
+        guilds.sort();
+        guilds.dedup();
+
+        let mut seeded = 0;
+        for guild_id in guilds {
+            let members: Vec<u64> = con.hkeys(wallet_key(guild_id)).await?;
+            if !members.is_empty() {
+                let _: () = con.sadd(granted_key(guild_id), &members).await?;
+                seeded += members.len();
+            }
+        }
+        let _: () = con
+            .set(GRANTS_SEEDED_KEY, chrono::Utc::now().timestamp())
+            .await?;
+        Ok(seeded)
+    }
+
+    /// Grants the daily coins, or returns the seconds left before they can be claimed again.
+    pub async fn claim_daily(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        amount: u64,
+    ) -> RedisResult<Result<u64, u64>> {
+        let mut con = self.con.clone();
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(daily_key(guild_id, user_id))
+            .arg(chrono::Utc::now().timestamp())
+            .arg("NX")
+            .arg("EX")
+            .arg(DAILY_COOLDOWN_SECS)
+            .query_async(&mut con)
+            .await?;
+        if claimed.is_none() {
+            let ttl: i64 = con.ttl(daily_key(guild_id, user_id)).await?;
+            return Ok(Err(ttl.max(0) as u64));
+        }
+
+        let balance: u64 = con
+            .hincr(wallet_key(guild_id), user_id.get(), amount)
+            .await?;
+        Ok(Ok(balance))
+    }
+
+    /// Moves coins between members, returns `false` if `from` can't pay.
+    pub async fn transfer(
+        &self,
+        guild_id: GuildId,
+        from: UserId,
+        to: UserId,
+        amount: u64,
+    ) -> RedisResult<bool> {
+        let moved: i64 = Script::new(TRANSFER_SCRIPT)
+            .key(wallet_key(guild_id))
+            .arg(from.get())
+            .arg(to.get())
+            .arg(amount)
+            .invoke_async(&mut self.con.clone())
+            .await?;
+        Ok(moved == 1)
+    }
+
+    /// Takes `amount` from each of `users` and holds it for the duel posted in `message_id`.
+    ///
+    /// Nobody pays unless everyone can, and a duel's coins are only held once.
+    pub async fn hold(
+        &self,
+        guild_id: GuildId,
+        message_id: MessageId,
+        amount: u64,
+        users: [UserId; 2],
+    ) -> RedisResult<Result<(), HoldError>> {
+        let script = Script::new(HOLD_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(wallet_key(guild_id))
+            .key(escrow_key(guild_id))
+            .arg(message_id.get())
+            .arg(amount);
+        for user_id in users {
+            invocation.arg(user_id.get());
+        }
+        let result: i64 = invocation.invoke_async(&mut self.con.clone()).await?;
+
+        Ok(match result {
+            0 => Ok(()),
+            -1 => Err(HoldError::AlreadyHeld),
+            position => Err(HoldError::Insufficient(users[position as usize - 1])),
+        })
+    }
+
+    /// Pays out the coins held for a duel, each user getting the stake times their share.
+    ///
+    /// Returns the stake, 0 if nothing was held (or it was already paid out).
+    pub async fn release(
+        &self,
+        guild_id: GuildId,
+        message_id: MessageId,
+        shares: &[(UserId, u64)],
+    ) -> RedisResult<u64> {
+        let script = Script::new(RELEASE_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(wallet_key(guild_id))
+            .key(escrow_key(guild_id))
+            .arg(message_id.get());
+        for (user_id, share) in shares {
+            invocation.arg(user_id.get()).arg(*share);
+        }
+        invocation.invoke_async(&mut self.con.clone()).await
+    }
+}
@@ -22,22 +22,46 @@ const client = new tmi.Client({
   channels: [process.env.TWITCH_CHANNEL]
 });
 
+// Twitch channels picked by Discord servers with `/config twitch_channel`.
+const twitchChannelsKey = 'twitch:channels';
+
 client.commands = [];
 client.prefix = config.twitchPrefix;
 client.channel = 'twitch_events';
 client.redisClient = redisClient;
 client.configColor = config.color;
+client.roomStates = {};
+client.roomStateOf = (channel) => client.roomStates[channel] || { slow: 0, emoteOnly: false, followersOnly: -1, subsOnly: false, uniqueChat: false };
 
 redisEvents(client, new Redis(redisUrl));
 loadEvents(client);
 loadCommands(client);
 
-client.connect();
+redisClient.smembers(twitchChannelsKey)
+  .then(channels => {
+    for (const channel of channels) {
+      if (!client.opts.channels.includes(`#${channel}`)) {
+        client.opts.channels.push(`#${channel}`);
+      }
+    }
+  })
+  .catch(error => console.error('[❌]Failed to load the Twitch channels of Discord servers:', error))
+  .finally(() => client.connect());
 
-process.on("SIGINT", () => {
-  redisClient.quit();
+// `docker compose down` sends SIGTERM, Ctrl+C sends SIGINT.
+const shutdown = async (signal) => {
+  console.log(`[⏳]Received ${signal}, shutting down...`);
+  try {
+    await client.disconnect();
+    console.log("Twitch client disconnected.");
+  } catch (error) {
+    console.error("Error disconnecting from Twitch:", error);
+  }
+  // QUIT waits for the replies of commands already sent, e.g. events being published.
+  await redisClient.quit();
   console.log("Redis client disconnected.");
-  client.disconnect();
-  console.log("Twitch client disconnected.");
   process.exit();
-});
+};
+
+process.on("SIGINT", shutdown);
+process.on("SIGTERM", shutdown);
@@ -0,0 +1,31 @@
+const actions = {
+  slow: (twitchClient, channel, command) => command.enabled
+    ? twitchClient.slow(channel, command.seconds).then(() => ({ slow: command.seconds }))
+    : twitchClient.slowoff(channel).then(() => ({ slow: 0 })),
+  emoteOnly: (twitchClient, channel, command) => (command.enabled
+    ? twitchClient.emoteonly(channel)
+    : twitchClient.emoteonlyoff(channel)).then(() => ({ emoteOnly: command.enabled })),
+  followersOnly: (twitchClient, channel, command) => command.enabled
+    ? twitchClient.followersonly(channel, command.minutes).then(() => ({ followersOnly: command.minutes }))
+    : twitchClient.followersonlyoff(channel).then(() => ({ followersOnly: -1 })),
+  subsOnly: (twitchClient, channel, command) => (command.enabled
+    ? twitchClient.subscribers(channel)
+    : twitchClient.subscribersoff(channel)).then(() => ({ subsOnly: command.enabled })),
+  uniqueChat: (twitchClient, channel, command) => (command.enabled
+    ? twitchClient.r9kbeta(channel)
+    : twitchClient.r9kbetaoff(channel)).then(() => ({ uniqueChat: command.enabled })),
+};
+
+module.exports = async (twitchClient, channel, command) => {
+  const action = actions[command.action];
+
+  if (!action) {
+    throw new Error(`Unknown chat setting "${command.action}"`);
+  }
+
+  const changes = await action(twitchClient, channel, command);
+  const name = channel.replace(/^#/, "").toLowerCase();
+  twitchClient.roomStates[name] = { ...twitchClient.roomStateOf(name), ...changes };
+
+  return twitchClient.roomStates[name];
+}
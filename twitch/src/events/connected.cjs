@@ -1,5 +1,6 @@
 module.exports = async (twitchClient, address, port) => {
   console.log(`[Twitch Bot] Connected to ${address}:${port}`);
+  twitchClient.connectedAt = Date.now();
   twitchClient.color(twitchClient.configColor)
     .then((data) => {
       console.log(`Client color is set to ${data}`);
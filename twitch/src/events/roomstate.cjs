@@ -0,0 +1,13 @@
+module.exports = async (twitchClient, channel, state) => {
+  const name = channel.replace(/^#/, "").toLowerCase();
+  const roomState = { ...twitchClient.roomStateOf(name) };
+
+  if ("slow" in state) roomState.slow = Number(state.slow) || 0;
+  if ("emote-only" in state) roomState.emoteOnly = state["emote-only"] === true || state["emote-only"] === "1";
+  if ("followers-only" in state) roomState.followersOnly = state["followers-only"] === false ? -1 : Number(state["followers-only"]);
+  if ("subs-only" in state) roomState.subsOnly = state["subs-only"] === true || state["subs-only"] === "1";
+  if ("r9k" in state) roomState.uniqueChat = state.r9k === true || state.r9k === "1";
+
+  twitchClient.roomStates[name] = roomState;
+  console.log(`[Twitch Bot] Room state of ${channel} updated`);
+}
@@ -0,0 +1,22 @@
+const actions = {
+  timeout: (twitchClient, channel, command) =>
+    twitchClient.timeout(channel, command.username, command.duration, command.reason || ""),
+  ban: (twitchClient, channel, command) =>
+    twitchClient.ban(channel, command.username, command.reason || ""),
+  unban: (twitchClient, channel, command) =>
+    twitchClient.unban(channel, command.username),
+  delete: (twitchClient, channel, command) =>
+    twitchClient.deletemessage(channel, command.messageId),
+  clear: (twitchClient, channel) =>
+    twitchClient.clear(channel),
+};
+
+module.exports = async (twitchClient, channel, command) => {
+  const action = actions[command.action];
+
+  if (!action) {
+    throw new Error(`Unknown moderation action "${command.action}"`);
+  }
+
+  await action(twitchClient, channel, command);
+}
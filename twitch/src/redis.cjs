@@ -1,7 +1,9 @@
+const rpc = require("./rpc.cjs");
+
 module.exports = async (twitchClient, redisClient) => {
   const channelName = "discord_messages";
 
-  redisClient.subscribe(channelName, (err, count) => {
+  redisClient.subscribe(channelName, rpc.requestChannel, (err, count) => {
     if (err) {
       console.error("Failed to subscribe: %s", err.message);
     } else {
@@ -10,14 +12,23 @@ module.exports = async (twitchClient, redisClient) => {
   });
 
   redisClient.on("message", (channel, message) => {
-    if (channel === channelName) {
+    if (channel === rpc.requestChannel) {
+      rpc.handle(twitchClient, message);
+    } else if (channel === channelName) {
       console.log(`Received message from channel "${channel}": ${message}`);
 
-      const tuple = message.split("|");
+      let bridged;
+      try {
+        bridged = JSON.parse(message);
+      } catch {
+        // Older Discord bots publish "content|author".
+        const tuple = message.split("|");
+        bridged = { channel: null, content: tuple[0], author: tuple[1] };
+      }
 
-      const twitchMessage = `~Discord~ ${tuple[1]}: ${tuple[0]}`;
+      const twitchMessage = `~Discord~ ${bridged.author}: ${bridged.content}`;
 
-      twitchClient.say(process.env.TWITCH_CHANNEL, twitchMessage)
+      twitchClient.say(bridged.channel || process.env.TWITCH_CHANNEL, twitchMessage)
         .then(() => {
           console.log(`Relayed message from Discord to Twitch: "${twitchMessage}"`);
         })
@@ -0,0 +1,68 @@
+const moderation = require("./moderation.cjs");
+const chatSettings = require("./chatSettings.cjs");
+
+const requestChannel = "twitch_rpc";
+
+const handlers = {
+  ping: async (twitchClient) => {
+    const [latency] = await twitchClient.ping();
+    return { type: "pong", params: { latencyMs: Math.round(latency * 1000) } };
+  },
+  status: async (twitchClient) => ({
+    type: "status",
+    params: {
+      state: twitchClient.readyState(),
+      channels: twitchClient.getChannels(),
+      uptimeSecs: twitchClient.connectedAt ? Math.floor((Date.now() - twitchClient.connectedAt) / 1000) : 0,
+    },
+  }),
+  moderation: async (twitchClient, params, channel) => {
+    await moderation(twitchClient, channel, params);
+    return { type: "done" };
+  },
+  chatSettings: async (twitchClient, params, channel) => {
+    const roomState = await chatSettings(twitchClient, channel, params);
+    return { type: "roomState", params: roomState };
+  },
+  join: async (twitchClient, params) => {
+    await twitchClient.join(params.channel);
+    return { type: "done" };
+  },
+  part: async (twitchClient, params) => {
+    await twitchClient.part(params.channel);
+    return { type: "done" };
+  },
+};
+
+const handle = async (twitchClient, payload) => {
+  let envelope;
+
+  try {
+    envelope = JSON.parse(payload);
+  } catch (error) {
+    console.error("[RPC] Failed to parse request:", error);
+    return;
+  }
+
+  const { id, replyTo, issuedBy, channel, request } = envelope;
+  const reply = { id, ok: true, error: null, response: null };
+
+  try {
+    const handler = handlers[request.type];
+    if (!handler) {
+      throw new Error(`Unknown request type "${request.type}"`);
+    }
+    reply.response = await handler(twitchClient, request.params, channel || process.env.TWITCH_CHANNEL);
+    console.log(`[RPC] Handled ${request.type} request for ${issuedBy}`);
+  } catch (error) {
+    reply.ok = false;
+    reply.error = typeof error === "string" ? error : error.message;
+    console.error(`[RPC] Error handling ${request.type} request:`, reply.error);
+  }
+
+  if (replyTo) {
+    twitchClient.redisClient.publish(replyTo, JSON.stringify(reply));
+  }
+}
+
+module.exports = { requestChannel, handle };